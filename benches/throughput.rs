@@ -0,0 +1,129 @@
+//! 数据转发路径的吞吐量基准: 服务端帧解码到投递本地目标, 以及本地数据编码为服务端帧
+//!
+//! 每项基准分别以 `Bytes` 与改为 `Bytes` 之前的 `Vec<u8>` 方式运行, 后者在每个方向多复制一次数据:
+//! 解码时 prost 把数据复制进 `Vec<u8>`, 读取本地目标后以 `to_vec` 构造消息
+//!
+//! 运行: `cargo bench --bench throughput`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use ldd_nat_cross_rclient::{
+    core::transfer_message::TransferDataMessage, model::message::ProtocolMessage,
+    net::codec::TransferMessageCodec,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// 每轮转发的数据总量
+const TOTAL: usize = 256 * 1024 * 1024;
+
+/// 每项基准的最短运行时间
+const MIN_DURATION: Duration = Duration::from_secs(2);
+
+/// 数据在转发路径上的表示方式
+#[derive(Debug, Clone, Copy)]
+enum Payload {
+    /// 从接收缓冲区切出或直接引用读取缓冲区, 不复制
+    Bytes,
+    /// 改为 `Bytes` 之前的方式, 复制为独立的 `Vec<u8>`
+    Vec,
+}
+
+impl Payload {
+    fn carry(self, data: Bytes) -> Bytes {
+        match self {
+            Payload::Bytes => data,
+            Payload::Vec => Bytes::from(data.to_vec()),
+        }
+    }
+}
+
+fn transfer(data: Bytes) -> TransferDataMessage {
+    ProtocolMessage::Transfer {
+        license_key: String::from("license"),
+        visitor_id: String::from("visitor"),
+        data,
+        compressed: false,
+    }
+    .into()
+}
+
+/// 服务端 -> 本地目标: 解帧、转换为协议消息并取出数据
+fn inbound(payload: Payload, payload_size: usize) -> f64 {
+    let mut codec = TransferMessageCodec::new();
+    let mut frames = BytesMut::new();
+    for _ in 0..TOTAL / payload_size {
+        codec
+            .encode(transfer(Bytes::from(vec![7u8; payload_size])), &mut frames)
+            .unwrap();
+    }
+    let frames = frames.freeze();
+    measure(|| {
+        let mut src = BytesMut::from(&frames[..]);
+        let mut delivered = 0;
+        while let Some(message) = codec.decode(&mut src).unwrap() {
+            if let ProtocolMessage::Transfer { data, .. } =
+                ProtocolMessage::try_from(message).unwrap()
+            {
+                delivered += black_box(payload.carry(data)).len();
+            }
+        }
+        delivered
+    })
+}
+
+/// 本地目标 -> 服务端: 按读取块构造协议消息并编码成帧
+fn outbound(payload: Payload, chunk_size: usize) -> f64 {
+    let mut codec = TransferMessageCodec::new();
+    let source = Bytes::from(vec![7u8; TOTAL]);
+    let mut dst = BytesMut::with_capacity(TOTAL + TOTAL / chunk_size * 64);
+    measure(|| {
+        dst.clear();
+        let mut sent = 0;
+        for offset in (0..TOTAL).step_by(chunk_size) {
+            let chunk = payload.carry(source.slice(offset..offset + chunk_size));
+            codec.encode(transfer(chunk), &mut dst).unwrap();
+            sent += chunk_size;
+        }
+        black_box(&dst);
+        sent
+    })
+}
+
+/// 反复运行直到超过最短运行时间, 返回 MiB/s
+///
+/// 入站基准每轮都会复制一次帧缓冲, 模拟从连接读取, 两种方式中该开销相同
+fn measure(mut round: impl FnMut() -> usize) -> f64 {
+    let start = Instant::now();
+    let mut bytes = 0;
+    while start.elapsed() < MIN_DURATION {
+        bytes += round();
+    }
+    bytes as f64 / start.elapsed().as_secs_f64() / (1024.0 * 1024.0)
+}
+
+fn main() {
+    for payload in [Payload::Vec, Payload::Bytes] {
+        for size in [8 * 1024, 64 * 1024] {
+            println!(
+                "inbound  {:<5} {:>6} B/frame: {:>9.1} MiB/s",
+                format!("{:?}", payload),
+                size,
+                inbound(payload, size)
+            );
+        }
+    }
+    for payload in [Payload::Vec, Payload::Bytes] {
+        for size in [8 * 1024, 64 * 1024] {
+            println!(
+                "outbound {:<5} {:>6} B/chunk: {:>9.1} MiB/s",
+                format!("{:?}", payload),
+                size,
+                outbound(payload, size)
+            );
+        }
+    }
+}
@@ -8,6 +8,7 @@ fn main() {
 
     prost_build::Config::new()
         .out_dir(out_dir) // 指定输出目录
+        .bytes([".transfer_message.TransferDataMessage.data"]) // 数据以 Bytes 表示, 解码时直接切片接收缓冲区
         .compile_protos(
             &[
                 "proto/cmd_type.proto",
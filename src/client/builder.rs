@@ -0,0 +1,72 @@
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    client::{handle::ClientHandle, runtime::run},
+    config::{client::ClientConfig, log::MessageLogConfig},
+};
+
+/// 默认的状态事件缓冲数量
+const DEFAULT_EVENT_CAPACITY: usize = 64;
+
+/// 穿透客户端
+#[derive(Debug)]
+pub struct Client {
+    config: ClientConfig,
+    event_capacity: usize,
+    message_log: MessageLogConfig,
+}
+
+impl Client {
+    pub fn builder(config: ClientConfig) -> ClientBuilder {
+        ClientBuilder::new(config)
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// 在当前 tokio 运行时中启动客户端, 返回用于控制客户端的句柄
+    pub fn start(self) -> ClientHandle {
+        let (cmd_tx, cmd_rx) = mpsc::channel(32);
+        let (event_tx, _) = broadcast::channel(self.event_capacity);
+        let task = tokio::spawn(run(self.config, self.message_log, cmd_rx, event_tx.clone()));
+        ClientHandle::new(cmd_tx, event_tx, task)
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    event_capacity: usize,
+    message_log: MessageLogConfig,
+}
+
+impl ClientBuilder {
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            message_log: MessageLogConfig::default(),
+        }
+    }
+
+    /// 状态事件的缓冲数量, 订阅方消费过慢时旧事件会被丢弃
+    pub fn event_capacity(mut self, event_capacity: usize) -> Self {
+        self.event_capacity = event_capacity.max(1);
+        self
+    }
+
+    /// 与服务端之间消息的日志详细程度, 默认仅记录摘要
+    pub fn message_log(mut self, message_log: MessageLogConfig) -> Self {
+        self.message_log = message_log;
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client {
+            config: self.config,
+            event_capacity: self.event_capacity,
+            message_log: self.message_log,
+        }
+    }
+}
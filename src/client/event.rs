@@ -0,0 +1,29 @@
+use crate::model::proxy::ProxyConfig;
+
+/// 客户端状态事件
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// 已与服务端建立连接
+    Connected,
+    /// 认证通过
+    Authenticated,
+    /// 认证失败
+    AuthFailed(String),
+    /// 服务端已开放隧道端口
+    TunnelOpened(ProxyConfig),
+    /// 隧道开放失败或被服务端关闭
+    TunnelFailed { proxy: ProxyConfig, reason: String },
+    /// 隧道已按请求关闭
+    TunnelClosed(ProxyConfig),
+    /// 与服务端的连接断开
+    Disconnected(String),
+    /// 即将进行第 attempt 次重连
+    Reconnecting {
+        attempt: u32,
+        delay: std::time::Duration,
+    },
+    /// 正在优雅退出, 等待剩余的访问者连接结束
+    Draining(usize),
+    /// 客户端已停止
+    Stopped,
+}
@@ -0,0 +1,319 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bytes::Bytes;
+use tokio::{
+    sync::{mpsc, Notify, Semaphore},
+    time::{self, Duration},
+};
+
+/// 默认的单个访问者接收窗口(字节)
+pub const DEFAULT_FLOW_WINDOW: u32 = 256 * 1024;
+
+/// 服务端通告的接收窗口下限(字节), 更小的窗口视为协议错误
+pub const MIN_FLOW_WINDOW: u32 = 4 * 1024;
+
+/// 单个访问者的双向流量控制窗口
+///
+/// - 接收方向: 服务端最多发送 `recv_limit` 字节尚未写入本地目标的数据,
+///   本端每写入半个窗口即以 WINDOW_UPDATE 归还额度
+/// - 发送方向: 每条 TRANSFER 消耗服务端通告的额度, 额度不足时暂停读取本地目标
+///
+/// 服务端不支持流量控制时不限制发送, 也不发送 WINDOW_UPDATE, 接收窗口仅作为缓冲上限:
+/// 缓冲已满时分发循环限时等待本地目标消费, 由此对整个连接施加背压
+#[derive(Debug)]
+pub struct FlowWindow {
+    recv_limit: usize,
+    /// 已收到但尚未写入本地目标的字节数
+    recv_buffered: AtomicUsize,
+    /// 已写入本地目标但尚未归还给服务端的字节数
+    recv_consumed: AtomicUsize,
+    /// 本地目标消费数据后通知等待缓冲空间的分发循环
+    recv_space: Notify,
+    /// 向服务端发送的额度, 服务端不支持流量控制时为 None
+    send_credit: Option<Semaphore>,
+    /// 服务端通告的初始窗口, 单条数据不能超过该长度, 否则永远等不到足够的额度
+    send_limit: Option<usize>,
+}
+
+impl FlowWindow {
+    /// `peer_window` 为服务端通告的初始发送额度, None 表示服务端不支持流量控制
+    pub fn new(recv_limit: u32, peer_window: Option<u32>) -> Self {
+        Self {
+            recv_limit: recv_limit as usize,
+            recv_buffered: AtomicUsize::new(0),
+            recv_consumed: AtomicUsize::new(0),
+            recv_space: Notify::new(),
+            send_credit: peer_window.map(|window| Semaphore::new(window as usize)),
+            send_limit: peer_window.map(|window| window as usize),
+        }
+    }
+
+    /// 是否与服务端协商了流量控制
+    pub fn is_negotiated(&self) -> bool {
+        self.send_credit.is_some()
+    }
+
+    /// 单条发往服务端的数据的最大长度, None 表示不限制
+    pub fn max_send_size(&self) -> Option<usize> {
+        self.send_limit
+    }
+
+    /// 为收到的数据占用接收窗口, 超出窗口时返回 false
+    fn try_reserve(&self, n: usize) -> bool {
+        self.recv_buffered
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |buffered| {
+                buffered
+                    .checked_add(n)
+                    .filter(|total| *total <= self.recv_limit)
+            })
+            .is_ok()
+    }
+
+    /// 等待接收窗口有足够的空间; 缓冲为空时总是接受, 单条超过窗口的数据不会永远等待
+    async fn reserve(&self, n: usize) {
+        loop {
+            let reserved = self
+                .recv_buffered
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |buffered| {
+                    buffered
+                        .checked_add(n)
+                        .filter(|total| buffered == 0 || *total <= self.recv_limit)
+                })
+                .is_ok();
+            if reserved {
+                return;
+            }
+            self.recv_space.notified().await;
+        }
+    }
+
+    /// 数据已写入本地目标, 累计达到半个窗口时返回需要归还给服务端的额度
+    fn release(&self, n: usize) -> Option<u32> {
+        self.recv_buffered.fetch_sub(n, Ordering::AcqRel);
+        self.recv_space.notify_one();
+        if !self.is_negotiated() {
+            return None;
+        }
+        let consumed = self.recv_consumed.fetch_add(n, Ordering::AcqRel) + n;
+        if consumed < self.recv_limit / 2 {
+            return None;
+        }
+        let increment = self.recv_consumed.swap(0, Ordering::AcqRel);
+        (increment > 0).then_some(increment as u32)
+    }
+
+    /// 等待足够的发送额度并扣除; 服务端不支持流量控制时立即返回
+    pub async fn acquire(&self, n: usize) {
+        let Some(send_credit) = &self.send_credit else {
+            return;
+        };
+        if n == 0 {
+            return;
+        }
+        // 单条数据超过 u32 时分段扣除
+        let mut remaining = n;
+        while remaining > 0 {
+            let chunk = remaining.min(u32::MAX as usize) as u32;
+            match send_credit.acquire_many(chunk).await {
+                Ok(permit) => permit.forget(),
+                // 信号量不会被关闭
+                Err(_) => return,
+            }
+            remaining -= chunk as usize;
+        }
+    }
+
+    /// 服务端归还了发送额度
+    pub fn grant(&self, increment: u32) {
+        if let Some(send_credit) = &self.send_credit {
+            // 防止异常的服务端使额度溢出
+            let room = Semaphore::MAX_PERMITS - send_credit.available_permits();
+            send_credit.add_permits((increment as usize).min(room));
+        }
+    }
+}
+
+/// 服务端转发给访问者的一段数据
+#[derive(Debug)]
+pub struct Payload {
+    /// 线路上的数据, 接收窗口按其长度计算
+    pub data: Bytes,
+    /// 数据经过压缩, 写入本地目标前需要解压
+    pub compressed: bool,
+}
+
+/// 向访问者投递数据失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliverError {
+    /// 访问者已关闭
+    Closed,
+    /// 服务端发送的数据超出了接收窗口
+    WindowExceeded,
+    /// 本地目标未在限定时间内消费缓冲的数据
+    Stalled,
+}
+
+/// 分发循环一侧的访问者通道, 投递数据从不等待
+#[derive(Debug, Clone)]
+pub struct VisitorSender {
+    tx: mpsc::UnboundedSender<Payload>,
+    window: Arc<FlowWindow>,
+}
+
+impl VisitorSender {
+    /// 投递服务端转发的数据; 缓冲由接收窗口限制, 因此无需等待本地目标
+    pub fn deliver(&self, payload: Payload) -> Result<(), DeliverError> {
+        if self.tx.is_closed() {
+            return Err(DeliverError::Closed);
+        }
+        let n = payload.data.len();
+        if !self.window.try_reserve(n) {
+            return Err(DeliverError::WindowExceeded);
+        }
+        self.tx.send(payload).map_err(|_| {
+            self.window.recv_buffered.fetch_sub(n, Ordering::AcqRel);
+            DeliverError::Closed
+        })
+    }
+
+    /// 投递服务端转发的数据, 接收窗口已满时最多等待 `wait` 让本地目标消费; 用于不支持流量控制的服务端
+    pub async fn deliver_wait(&self, payload: Payload, wait: Duration) -> Result<(), DeliverError> {
+        let n = payload.data.len();
+        tokio::select! {
+            biased;
+            _ = self.window.reserve(n) => {}
+            _ = self.tx.closed() => return Err(DeliverError::Closed),
+            _ = time::sleep(wait) => return Err(DeliverError::Stalled),
+        }
+        self.tx.send(payload).map_err(|_| {
+            self.window.recv_buffered.fetch_sub(n, Ordering::AcqRel);
+            DeliverError::Closed
+        })
+    }
+
+    pub fn window(&self) -> &Arc<FlowWindow> {
+        &self.window
+    }
+}
+
+/// 访问者任务一侧的通道
+#[derive(Debug)]
+pub struct VisitorReceiver {
+    rx: mpsc::UnboundedReceiver<Payload>,
+    window: Arc<FlowWindow>,
+}
+
+impl VisitorReceiver {
+    pub async fn recv(&mut self) -> Option<Payload> {
+        self.rx.recv().await
+    }
+
+    /// 数据已写入本地目标, `n` 为线路上的长度; 返回需要通过 WINDOW_UPDATE 归还的额度
+    pub fn consumed(&self, n: usize) -> Option<u32> {
+        self.window.release(n)
+    }
+
+    pub fn window(&self) -> &Arc<FlowWindow> {
+        &self.window
+    }
+}
+
+/// 创建访问者的数据通道, 两端共享同一个流量控制窗口
+pub fn channel(recv_limit: u32, peer_window: Option<u32>) -> (VisitorSender, VisitorReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let window = Arc::new(FlowWindow::new(recv_limit, peer_window));
+    (
+        VisitorSender {
+            tx,
+            window: window.clone(),
+        },
+        VisitorReceiver { rx, window },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(n: usize) -> Payload {
+        Payload {
+            data: Bytes::from(vec![0; n]),
+            compressed: false,
+        }
+    }
+
+    #[test]
+    fn try_reserve_accepts_up_to_the_limit() {
+        let window = FlowWindow::new(1024, Some(1024));
+        assert!(window.try_reserve(1000));
+        assert!(window.try_reserve(24));
+        assert!(!window.try_reserve(1));
+        window.release(24);
+        assert!(window.try_reserve(1));
+    }
+
+    #[test]
+    fn release_returns_increment_every_half_window() {
+        let window = FlowWindow::new(1024, Some(1024));
+        assert!(window.try_reserve(1024));
+        assert_eq!(window.release(511), None);
+        assert_eq!(window.release(1), Some(512));
+        assert_eq!(window.release(100), None);
+        assert_eq!(window.release(412), Some(512));
+    }
+
+    #[test]
+    fn release_without_negotiation_returns_nothing() {
+        let window = FlowWindow::new(1024, None);
+        assert!(window.try_reserve(1024));
+        assert_eq!(window.release(1024), None);
+    }
+
+    #[test]
+    fn grant_saturates_at_max_permits() {
+        let mut window = FlowWindow::new(1024, Some(1024));
+        window.send_credit = Some(Semaphore::new(Semaphore::MAX_PERMITS - 10));
+        // 异常的服务端归还超量的额度也不会使信号量溢出
+        window.grant(u32::MAX);
+        window.grant(1);
+        let available = window
+            .send_credit
+            .as_ref()
+            .map(Semaphore::available_permits);
+        assert_eq!(available, Some(Semaphore::MAX_PERMITS));
+    }
+
+    #[tokio::test]
+    async fn reserve_accepts_oversize_data_when_empty() {
+        let window = FlowWindow::new(1024, None);
+        window.reserve(4096).await;
+        assert_eq!(window.recv_buffered.load(Ordering::Acquire), 4096);
+        // 缓冲非空时超出窗口需要等待本地目标消费
+        let waiting = time::timeout(Duration::from_millis(50), window.reserve(1)).await;
+        assert!(waiting.is_err());
+        window.release(4096);
+        window.reserve(1024).await;
+    }
+
+    #[tokio::test]
+    async fn deliver_wait_gives_up_on_stalled_target() {
+        let (tx, mut rx) = channel(1024, None);
+        assert_eq!(tx.deliver_wait(payload(1024), Duration::ZERO).await, Ok(()));
+        assert_eq!(
+            tx.deliver_wait(payload(1), Duration::from_millis(20)).await,
+            Err(DeliverError::Stalled)
+        );
+        let received = rx.recv().await.map(|payload| payload.data.len());
+        assert_eq!(received, Some(1024));
+        rx.consumed(1024);
+        assert_eq!(tx.deliver_wait(payload(1), Duration::ZERO).await, Ok(()));
+        drop(rx);
+        assert_eq!(
+            tx.deliver_wait(payload(1), Duration::ZERO).await,
+            Err(DeliverError::Closed)
+        );
+    }
+}
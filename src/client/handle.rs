@@ -0,0 +1,118 @@
+use futures::{stream, Stream};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use crate::{
+    client::event::ClientEvent,
+    common::error::{ClientError, Result},
+    model::proxy::{ProxyConfig, ProxyDiff},
+};
+
+/// 发送给客户端运行时的指令
+#[derive(Debug)]
+pub(crate) enum ClientCommand {
+    AddProxy(ProxyConfig, oneshot::Sender<Result<()>>),
+    RemoveProxy(i32, oneshot::Sender<Result<()>>),
+    ReloadProxies(Vec<ProxyConfig>, oneshot::Sender<Result<ProxyDiff>>),
+    Shutdown,
+}
+
+/// 运行中客户端的句柄
+///
+/// 丢弃句柄不会停止客户端, 需要显式调用 [`ClientHandle::shutdown`]
+#[derive(Debug)]
+pub struct ClientHandle {
+    cmd_tx: mpsc::Sender<ClientCommand>,
+    event_tx: broadcast::Sender<ClientEvent>,
+    /// 运行时任务, 等待到退出结果后置为 None
+    task: Option<JoinHandle<Result<()>>>,
+}
+
+impl ClientHandle {
+    pub(crate) fn new(
+        cmd_tx: mpsc::Sender<ClientCommand>,
+        event_tx: broadcast::Sender<ClientEvent>,
+        task: JoinHandle<Result<()>>,
+    ) -> Self {
+        Self {
+            cmd_tx,
+            event_tx,
+            task: Some(task),
+        }
+    }
+
+    /// 新增代理, 已认证时立即向服务端请求开放端口
+    pub async fn add_proxy(&self, proxy_config: ProxyConfig) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(ClientCommand::AddProxy(proxy_config, tx))
+            .await?;
+        rx.await.map_err(|_| ClientError::ChannelClosed)?
+    }
+
+    /// 按服务端开放端口移除代理, 已认证时通知服务端关闭端口
+    pub async fn remove_proxy(&self, open_port: i32) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(ClientCommand::RemoveProxy(open_port, tx))
+            .await?;
+        rx.await.map_err(|_| ClientError::ChannelClosed)?
+    }
+
+    /// 以新的代理列表替换当前列表: 只开放新增的端口、关闭移除的端口, 未变化的隧道及其访问者不受影响
+    pub async fn reload_proxies(&self, proxies: Vec<ProxyConfig>) -> Result<ProxyDiff> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(ClientCommand::ReloadProxies(proxies, tx))
+            .await?;
+        rx.await.map_err(|_| ClientError::ChannelClosed)?
+    }
+
+    /// 订阅状态事件, 只能收到订阅之后产生的事件
+    pub fn events(&self) -> impl Stream<Item = ClientEvent> {
+        stream::unfold(self.event_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        log::warn!("状态事件消费过慢, 丢弃 {} 条事件", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// 优雅停止客户端并等待运行时退出
+    pub async fn shutdown(self) -> Result<()> {
+        self.request_shutdown().await;
+        self.join().await
+    }
+
+    /// 请求优雅停止但不等待: 关闭所有隧道并在 drainTimeout 内等待访问者结束;
+    /// 退出过程中再次请求时立即断开剩余的访问者
+    pub async fn request_shutdown(&self) {
+        // 运行时已退出时发送失败, 忽略即可
+        let _ = self.cmd_tx.send(ClientCommand::Shutdown).await;
+    }
+
+    /// 等待客户端退出, 认证失败或重连次数耗尽时返回错误
+    pub async fn join(mut self) -> Result<()> {
+        self.wait().await
+    }
+
+    /// 与 [`ClientHandle::join`] 相同, 但不消耗句柄, 便于在 `select!` 中与其他操作并行;
+    /// 退出结果只返回一次, 之后的调用返回 [`ClientError::AlreadyExited`]
+    pub async fn wait(&mut self) -> Result<()> {
+        let task = self.task.as_mut().ok_or(ClientError::AlreadyExited)?;
+        // 取消等待时保留任务, 拿到结果后才移除
+        let joined = task.await;
+        self.task = None;
+        match joined {
+            Ok(result) => result,
+            Err(e) => Err(ClientError::TaskFailed(e)),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod builder;
+pub mod event;
+pub mod flow;
+pub mod handle;
+pub mod process;
+pub mod registry;
+mod runtime;
+mod session;
@@ -0,0 +1,500 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        lookup_host,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream, UdpSocket,
+    },
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use crate::{
+    client::{
+        flow::{FlowWindow, Payload, VisitorReceiver},
+        registry::{Visitor, VisitorRegistry},
+    },
+    common::error::Result,
+    config::client::ClientConfig,
+    core::transfer_message::TransferDataMessage,
+    helper::{
+        compress::{self, Compressor},
+        e2e::{self, Opener, PresharedKey, Sealer},
+    },
+    model::{message::ProtocolMessage, protocol::ProtocolEnum, proxy::ProxyConfig},
+};
+
+/// UDP 数据报的最大长度
+pub const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+/// 每次从 TCP 目标读取的最大长度
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 为 TRANSFER 消息的元数据预留的长度
+const METADATA_HEADROOM: usize = 4 * 1024;
+
+/// 单条 TRANSFER 帧的最大长度: 一次读取(不超过一个数据报)的数据加上加密开销与元数据
+pub const MAX_TRANSFER_FRAME_SIZE: usize = MAX_DATAGRAM_SIZE + e2e::OVERHEAD + METADATA_HEADROOM;
+
+/// 访问者连接的超时、压缩与加密设置
+#[derive(Debug, Clone)]
+pub struct ProcessOptions {
+    /// 连接本地目标的超时
+    pub connect_timeout: Duration,
+    /// UDP 目标在该时间内双向都没有数据时视为会话结束
+    pub udp_idle_timeout: Duration,
+    /// 发往服务端的数据的压缩器, None 表示不压缩
+    pub compressor: Option<Compressor>,
+    /// 单条数据解压后的最大长度
+    pub max_payload_size: usize,
+    /// 端到端加密的预共享密钥, None 表示不加密
+    pub e2e_key: Option<PresharedKey>,
+}
+
+impl From<&ClientConfig> for ProcessOptions {
+    fn from(client_config: &ClientConfig) -> Self {
+        Self {
+            connect_timeout: client_config.get_connect_timeout(),
+            udp_idle_timeout: client_config.get_udp_idle_timeout(),
+            compressor: None,
+            max_payload_size: client_config.get_max_frame_size(),
+            e2e_key: None,
+        }
+    }
+}
+
+/// 与本地目标之间的连接
+enum Target {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+/// 建立与本地目标的连接, 并在后台任务中双向转发数据
+///
+/// 连接建立前服务端转发的数据暂存在 `rx` 中, 由流量控制窗口限制缓冲大小
+pub async fn process(
+    proxy_config: ProxyConfig,
+    license_key: String,
+    visitor: Weak<Visitor>,
+    rx: VisitorReceiver,
+    s_tx: mpsc::Sender<TransferDataMessage>,
+    visitors: Arc<VisitorRegistry>,
+    options: ProcessOptions,
+) -> Result<()> {
+    // 任务开始前服务端已断开该访问者
+    let visitor_id = match visitor.upgrade() {
+        Some(visitor) => visitor.visitor_id().to_string(),
+        None => return Ok(()),
+    };
+    let target_addr = format!("{}:{}", proxy_config.host(), proxy_config.port());
+    let connected = async {
+        // TCP 数据必须逐条连续到达, UDP 数据报允许丢失
+        let strict = proxy_config.protocol() == ProtocolEnum::TCP;
+        let cipher = options
+            .e2e_key
+            .as_ref()
+            .map(|key| e2e::stream(key, &visitor_id, strict))
+            .transpose()?;
+        let target = time::timeout(
+            options.connect_timeout,
+            connect_target(proxy_config.protocol(), &target_addr),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{:?} 内未能建立连接", options.connect_timeout),
+            ))
+        })?;
+        Ok::<_, io::Error>((target, cipher))
+    }
+    .await;
+    let (target, (sealer, opener)) = match connected {
+        Ok((target, cipher)) => (target, cipher.unzip()),
+        Err(e) => {
+            log::error!("连接目标服务 {} 失败: {}", target_addr, e);
+            // 发送disconnect
+            let disconnect_msg = ProtocolMessage::Disconnect {
+                license_key: license_key.clone(),
+                visitor_id: visitor_id.clone(),
+            };
+            s_tx.send(disconnect_msg.into()).await?;
+            return Err(e.into());
+        }
+    };
+    // 连接期间服务端已断开该访问者, 或同一 visitor_id 的新访问者替换了它
+    match visitor.upgrade() {
+        Some(registered) if visitors.contains(&registered) => registered.mark_connected(),
+        _ => {
+            log::info!("visitor_id {} 已在连接建立前断开", visitor_id);
+            return Ok(());
+        }
+    }
+    // 先发送连接建立消息给服务端, 对端需要据其中的盐派生发往本端的密钥
+    let connect_msg = ProtocolMessage::Connect {
+        license_key: license_key.clone(),
+        visitor_id: visitor_id.clone(),
+        proxy: proxy_config,
+        e2e_salt: opener.as_ref().map(|opener| hex::encode(opener.salt())),
+    };
+    s_tx.send(connect_msg.into()).await?;
+
+    let outbound = Outbound {
+        visitor: visitor.clone(),
+        window: rx.window().clone(),
+        compressor: options.compressor,
+        sealer,
+    };
+    let inbound = Inbound {
+        rx,
+        opener,
+        max_payload_size: options.max_payload_size,
+    };
+
+    // 读写两个方向在同一个任务中运行, 任一方向结束时另一方向随之取消
+    tokio::spawn(async move {
+        let closed_by = match target {
+            Target::Tcp(stream) => {
+                let (target_read, target_write) = stream.into_split();
+                tokio::select! {
+                    closed_by = read_target(target_read, outbound, &visitor_id, &license_key, &s_tx) => closed_by,
+                    closed_by = write_target(target_write, inbound, &visitor_id, &license_key, &s_tx) => closed_by,
+                }
+            }
+            Target::Udp(socket) => {
+                forward_udp(
+                    socket,
+                    outbound,
+                    inbound,
+                    &visitor_id,
+                    &license_key,
+                    &s_tx,
+                    options.udp_idle_timeout,
+                )
+                .await
+            }
+        };
+        if let ClosedBy::Local = closed_by {
+            // 只移除本任务的访问者; 已被移除或替换时服务端已知晓, 无需再通知
+            let removed = visitor
+                .upgrade()
+                .and_then(|visitor| visitors.remove_if(&visitor_id, &visitor));
+            if removed.is_none() {
+                return;
+            }
+            log::info!("visitor_id {} 的本地连接已关闭, 通知服务端断开", visitor_id);
+            let disconnect_msg = ProtocolMessage::Disconnect {
+                license_key,
+                visitor_id,
+            };
+            let _ = s_tx.send(disconnect_msg.into()).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// 访问者连接的关闭方
+enum ClosedBy {
+    /// 本地目标关闭了连接、读写出错或 UDP 会话空闲超时
+    Local,
+    /// 服务端断开了访问者, 或会话已失效
+    Remote,
+}
+
+/// 本地目标发往服务端方向的状态: 发送额度、压缩与加密
+struct Outbound {
+    /// 只持有弱引用, 访问者从注册表移除后通道随之关闭, 转发任务得以退出
+    visitor: Weak<Visitor>,
+    window: Arc<FlowWindow>,
+    compressor: Option<Compressor>,
+    sealer: Option<Sealer>,
+}
+
+impl Outbound {
+    /// 每次从本地目标读取的最大长度, 保证加密后的单条数据不超过服务端的窗口
+    fn max_read_size(&self) -> usize {
+        let overhead = if self.sealer.is_some() {
+            e2e::OVERHEAD
+        } else {
+            0
+        };
+        match self.window.max_send_size() {
+            Some(limit) => limit.saturating_sub(overhead).min(READ_BUFFER_SIZE),
+            None => READ_BUFFER_SIZE,
+        }
+    }
+
+    /// 按需加密或压缩从本地目标读取的数据, 等待发送额度后构造 TRANSFER 消息; 额度按线路上的长度扣除
+    async fn transfer_message(
+        &mut self,
+        data: Bytes,
+        visitor_id: &str,
+        license_key: &str,
+    ) -> io::Result<ProtocolMessage> {
+        // 加密与压缩不会同时开启, 见配置校验
+        let (data, compressed) = match (&mut self.sealer, &self.compressor) {
+            (Some(sealer), _) => (sealer.seal(&data)?, false),
+            (None, Some(compressor)) => compressor.compress(data),
+            (None, None) => (data, false),
+        };
+        self.window.acquire(data.len()).await;
+        Ok(ProtocolMessage::Transfer {
+            license_key: license_key.to_string(),
+            visitor_id: visitor_id.to_string(),
+            data,
+            compressed,
+        })
+    }
+
+    /// 构造并发送一条 TRANSFER 消息, `n` 为从本地目标读取的长度
+    async fn send(
+        &mut self,
+        data: Bytes,
+        n: usize,
+        visitor_id: &str,
+        license_key: &str,
+        s_tx: &mpsc::Sender<TransferDataMessage>,
+    ) -> std::result::Result<(), ClosedBy> {
+        let transfer_msg = self
+            .transfer_message(data, visitor_id, license_key)
+            .await
+            .map_err(|e| {
+                log::error!("加密 visitor_id {} 的数据失败: {}", visitor_id, e);
+                ClosedBy::Local
+            })?;
+        if let Err(e) = s_tx.send(transfer_msg.into()).await {
+            log::error!("发送转发消息失败: {:?}", e);
+            return Err(ClosedBy::Remote);
+        }
+        // 访问者已被移除
+        let visitor = self.visitor.upgrade().ok_or(ClosedBy::Remote)?;
+        visitor.record_out(n);
+        Ok(())
+    }
+}
+
+/// 服务端发往本地目标方向的状态: 接收窗口、解压与解密
+struct Inbound {
+    rx: VisitorReceiver,
+    opener: Option<Opener>,
+    /// 单条数据解压后的最大长度
+    max_payload_size: usize,
+}
+
+impl Inbound {
+    /// 取出写入本地目标的数据: 压缩过的数据先解压, 开启端到端加密时再解密并校验
+    fn decode(&mut self, payload: Payload, visitor_id: &str) -> Option<Bytes> {
+        let data = if payload.compressed {
+            match compress::decompress(&payload.data, self.max_payload_size) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::error!("解压 visitor_id {} 的数据失败: {}", visitor_id, e);
+                    return None;
+                }
+            }
+        } else {
+            payload.data
+        };
+        let Some(opener) = &mut self.opener else {
+            return Some(data);
+        };
+        match opener.open(&data) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                log::error!("visitor_id {} 的数据解密失败, 断开连接: {}", visitor_id, e);
+                None
+            }
+        }
+    }
+}
+
+/// 负责从目标服务读取数据，并构造 transfer 消息转发给服务端; 发送额度不足时暂停读取
+async fn read_target(
+    mut target_read: OwnedReadHalf,
+    mut outbound: Outbound,
+    visitor_id: &str,
+    license_key: &str,
+    s_tx: &mpsc::Sender<TransferDataMessage>,
+) -> ClosedBy {
+    let mut buffer = BytesMut::new();
+    let max_read_size = outbound.max_read_size();
+    loop {
+        // 已发出的数据释放后可复用同一块内存
+        buffer.reserve(max_read_size);
+        let n = match target_read
+            .read_buf(&mut (&mut buffer).limit(max_read_size))
+            .await
+        {
+            Ok(0) => return ClosedBy::Local, // 连接关闭
+            Ok(n) => n,
+            Err(e) => {
+                log::error!("从目标连接读取数据失败: {:?}", e);
+                return ClosedBy::Local;
+            }
+        };
+        if let Err(closed_by) = outbound
+            .send(buffer.split().freeze(), n, visitor_id, license_key, s_tx)
+            .await
+        {
+            return closed_by;
+        }
+    }
+}
+
+/// 负责从上层接收数据并写入目标服务, 写入后归还接收额度
+async fn write_target(
+    mut target_write: OwnedWriteHalf,
+    mut inbound: Inbound,
+    visitor_id: &str,
+    license_key: &str,
+    s_tx: &mpsc::Sender<TransferDataMessage>,
+) -> ClosedBy {
+    while let Some(payload) = inbound.rx.recv().await {
+        let n = payload.data.len();
+        let Some(data) = inbound.decode(payload, visitor_id) else {
+            return ClosedBy::Local;
+        };
+        if let Err(e) = target_write.write_all(&data).await {
+            log::error!("写入目标连接数据失败: {:?}", e);
+            return ClosedBy::Local;
+        }
+        if let Err(closed_by) =
+            send_window_update(inbound.rx.consumed(n), visitor_id, license_key, s_tx).await
+        {
+            return closed_by;
+        }
+    }
+    // 通道被关闭, 说明服务端已断开该访问者
+    ClosedBy::Remote
+}
+
+/// 按代理协议连接本地目标
+async fn connect_target(protocol: ProtocolEnum, target_addr: &str) -> io::Result<Target> {
+    match protocol {
+        ProtocolEnum::TCP => TcpStream::connect(target_addr).await.map(Target::Tcp),
+        ProtocolEnum::UDP => connect_udp(target_addr).await.map(Target::Udp),
+        ProtocolEnum::Unknown(other) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("不支持的协议: {}", other),
+        )),
+    }
+}
+
+/// 创建连接到目标地址的 UDP socket, 使用与目标相同的地址族
+async fn connect_udp(target_addr: &str) -> io::Result<UdpSocket> {
+    let addr = lookup_host(target_addr).await?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("无法解析地址 {}", target_addr),
+        )
+    })?;
+    let bind_addr: SocketAddr = match addr {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(addr).await?;
+    Ok(socket)
+}
+
+/// 接收一个数据报, 追加到缓冲区末尾
+async fn recv_datagram(socket: &UdpSocket, buffer: &mut BytesMut) -> io::Result<usize> {
+    buffer.reserve(MAX_DATAGRAM_SIZE);
+    socket.recv_buf(buffer).await
+}
+
+/// 在 UDP 目标与服务端之间转发数据报, 每条 TRANSFER 消息对应一个数据报
+async fn forward_udp(
+    socket: UdpSocket,
+    mut outbound: Outbound,
+    mut inbound: Inbound,
+    visitor_id: &str,
+    license_key: &str,
+    s_tx: &mpsc::Sender<TransferDataMessage>,
+    idle_timeout: Duration,
+) -> ClosedBy {
+    let mut buffer = BytesMut::new();
+    let max_read_size = outbound.max_read_size();
+    let idle = time::sleep(idle_timeout);
+    tokio::pin!(idle);
+    loop {
+        tokio::select! {
+            received = recv_datagram(&socket, &mut buffer) => {
+                let n = match received {
+                    Ok(n) => n,
+                    Err(e) => {
+                        log::error!("从 UDP 目标读取数据失败: {:?}", e);
+                        return ClosedBy::Local;
+                    }
+                };
+                // 数据报不能拆分, 超过服务端窗口的数据报只能丢弃
+                if n > max_read_size {
+                    log::warn!("visitor_id {} 的数据报长 {} 字节, 超过服务端窗口允许的 {} 字节, 已丢弃", visitor_id, n, max_read_size);
+                    buffer.clear();
+                    continue;
+                }
+                if let Err(closed_by) = outbound
+                    .send(buffer.split().freeze(), n, visitor_id, license_key, s_tx)
+                    .await
+                {
+                    return closed_by;
+                }
+            }
+            payload = inbound.rx.recv() => {
+                let payload = match payload {
+                    Some(payload) => payload,
+                    // 通道被关闭, 说明服务端已断开该访问者
+                    None => return ClosedBy::Remote,
+                };
+                let n = payload.data.len();
+                let Some(data) = inbound.decode(payload, visitor_id) else {
+                    return ClosedBy::Local;
+                };
+                if let Err(e) = socket.send(&data).await {
+                    log::error!("写入 UDP 目标数据失败: {:?}", e);
+                    return ClosedBy::Local;
+                }
+                if let Err(closed_by) =
+                    send_window_update(inbound.rx.consumed(n), visitor_id, license_key, s_tx).await
+                {
+                    return closed_by;
+                }
+            }
+            _ = &mut idle => {
+                log::info!("visitor_id {} 的 UDP 会话空闲超过 {:?}", visitor_id, idle_timeout);
+                return ClosedBy::Local;
+            }
+        }
+        idle.as_mut().reset(Instant::now() + idle_timeout);
+    }
+}
+
+/// 向服务端归还接收额度
+async fn send_window_update(
+    increment: Option<u32>,
+    visitor_id: &str,
+    license_key: &str,
+    s_tx: &mpsc::Sender<TransferDataMessage>,
+) -> std::result::Result<(), ClosedBy> {
+    let Some(increment) = increment else {
+        return Ok(());
+    };
+    let window_update = ProtocolMessage::WindowUpdate {
+        license_key: license_key.to_string(),
+        visitor_id: visitor_id.to_string(),
+        increment,
+    };
+    s_tx.send(window_update.into()).await.map_err(|e| {
+        log::error!("发送窗口更新失败: {:?}", e);
+        ClosedBy::Remote
+    })
+}
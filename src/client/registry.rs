@@ -0,0 +1,317 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{client::flow::VisitorSender, model::proxy::ProxyConfig};
+
+/// 分片数量, 访问者按 visitor_id 的哈希分散到各分片, 降低锁竞争
+const SHARDS: usize = 32;
+
+/// 单个访问者的状态
+#[derive(Debug)]
+pub struct Visitor {
+    visitor_id: String,
+    open_port: i32,
+    /// 本地目标地址
+    target: String,
+    sender: VisitorSender,
+    /// 服务端转发给本地目标的字节数
+    bytes_in: AtomicU64,
+    /// 本地目标发往服务端的字节数
+    bytes_out: AtomicU64,
+    created_at: Instant,
+    /// 最近一次收发数据距 `created_at` 的毫秒数
+    last_active: AtomicU64,
+    /// 本地目标的连接是否已建立
+    connected: AtomicBool,
+}
+
+impl Visitor {
+    pub fn new(visitor_id: String, proxy_config: &ProxyConfig, sender: VisitorSender) -> Self {
+        Self {
+            visitor_id,
+            open_port: proxy_config.open_port(),
+            target: format!("{}:{}", proxy_config.host(), proxy_config.port()),
+            sender,
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            created_at: Instant::now(),
+            last_active: AtomicU64::new(0),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    pub fn visitor_id(&self) -> &str {
+        &self.visitor_id
+    }
+
+    pub fn open_port(&self) -> i32 {
+        self.open_port
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn sender(&self) -> &VisitorSender {
+        &self.sender
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    pub fn last_active(&self) -> Instant {
+        self.created_at + Duration::from_millis(self.last_active.load(Ordering::Relaxed))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    /// 本地目标的连接已建立
+    pub fn mark_connected(&self) {
+        self.connected.store(true, Ordering::Release);
+    }
+
+    /// 记录服务端转发给本地目标的数据
+    pub fn record_in(&self, n: usize) {
+        self.bytes_in.fetch_add(n as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// 记录本地目标发往服务端的数据
+    pub fn record_out(&self, n: usize) {
+        self.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    fn log_closed(&self) {
+        log::info!(
+            "关闭 visitor_id {} 对应的通道, 目标 {}, 收 {} 字节, 发 {} 字节, 持续 {:?}",
+            self.visitor_id,
+            self.target,
+            self.bytes_in(),
+            self.bytes_out(),
+            self.created_at.elapsed()
+        );
+    }
+
+    fn touch(&self) {
+        let elapsed = self.created_at.elapsed().as_millis() as u64;
+        self.last_active.fetch_max(elapsed, Ordering::Relaxed);
+    }
+}
+
+type Shard = RwLock<HashMap<String, Arc<Visitor>>>;
+
+/// 访问者注册表: visitor_id -> 访问者状态
+///
+/// 由会话持有, 按 visitor_id 分片加锁且从不跨 await 持锁, 不同访问者的查找互不阻塞
+#[derive(Debug)]
+pub struct VisitorRegistry {
+    shards: Box<[Shard]>,
+    hasher: RandomState,
+}
+
+impl VisitorRegistry {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard(&self, visitor_id: &str) -> &Shard {
+        let index = self.hasher.hash_one(visitor_id) as usize % self.shards.len();
+        &self.shards[index]
+    }
+
+    /// 注册访问者, 同一 visitor_id 的旧访问者被替换并关闭
+    pub fn insert(&self, visitor: Visitor) -> Arc<Visitor> {
+        let visitor = Arc::new(visitor);
+        let replaced = write(self.shard(&visitor.visitor_id))
+            .insert(visitor.visitor_id.clone(), visitor.clone());
+        if let Some(replaced) = replaced {
+            log::warn!("visitor_id {} 重复, 关闭旧的连接", replaced.visitor_id);
+        }
+        visitor
+    }
+
+    pub fn get(&self, visitor_id: &str) -> Option<Arc<Visitor>> {
+        read(self.shard(visitor_id)).get(visitor_id).cloned()
+    }
+
+    /// 访问者仍在注册表中, 且未被同一 visitor_id 的新访问者替换
+    pub fn contains(&self, visitor: &Arc<Visitor>) -> bool {
+        read(self.shard(&visitor.visitor_id))
+            .get(&visitor.visitor_id)
+            .is_some_and(|current| Arc::ptr_eq(current, visitor))
+    }
+
+    /// 移除访问者并关闭其通道, process 任务随之退出
+    pub fn remove(&self, visitor_id: &str) -> Option<Arc<Visitor>> {
+        let visitor = write(self.shard(visitor_id)).remove(visitor_id)?;
+        visitor.log_closed();
+        Some(visitor)
+    }
+
+    /// 仅当注册的仍是 `visitor` 时移除, 不会误删同一 visitor_id 的新访问者
+    pub fn remove_if(&self, visitor_id: &str, visitor: &Arc<Visitor>) -> Option<Arc<Visitor>> {
+        let mut shard = write(self.shard(visitor_id));
+        if !shard
+            .get(visitor_id)
+            .is_some_and(|current| Arc::ptr_eq(current, visitor))
+        {
+            return None;
+        }
+        let visitor = shard.remove(visitor_id)?;
+        drop(shard);
+        visitor.log_closed();
+        Some(visitor)
+    }
+
+    /// 关闭某个开放端口下的全部访问者, 返回关闭的数量
+    pub fn remove_port(&self, open_port: i32) -> usize {
+        let removed = self.retain(|visitor| visitor.open_port != open_port).len();
+        if removed > 0 {
+            log::info!("关闭开放端口 {} 下的 {} 个访问者通道", open_port, removed);
+        }
+        removed
+    }
+
+    /// 只保留满足条件的访问者, 返回被移除的访问者
+    pub fn retain(&self, mut keep: impl FnMut(&Visitor) -> bool) -> Vec<Arc<Visitor>> {
+        let mut removed = Vec::new();
+        for shard in self.shards.iter() {
+            write(shard).retain(|_, visitor| {
+                let kept = keep(visitor);
+                if !kept {
+                    removed.push(visitor.clone());
+                }
+                kept
+            });
+        }
+        removed
+    }
+
+    /// 移除全部访问者
+    pub fn drain(&self) -> Vec<Arc<Visitor>> {
+        self.retain(|_| false)
+    }
+
+    /// 当前全部访问者的快照
+    pub fn visitors(&self) -> Vec<Arc<Visitor>> {
+        self.shards
+            .iter()
+            .flat_map(|shard| read(shard).values().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).is_empty())
+    }
+}
+
+impl Default for VisitorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 持锁期间不会 panic, 锁中毒时继续使用其中的数据
+fn read(shard: &Shard) -> RwLockReadGuard<'_, HashMap<String, Arc<Visitor>>> {
+    shard.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write(shard: &Shard) -> RwLockWriteGuard<'_, HashMap<String, Arc<Visitor>>> {
+    shard.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::flow, model::protocol::ProtocolEnum};
+
+    fn visitor(visitor_id: &str, open_port: i32) -> Visitor {
+        let proxy_config =
+            ProxyConfig::new("127.0.0.1".to_string(), 8080, open_port, ProtocolEnum::TCP);
+        let (tx, _rx) = flow::channel(flow::DEFAULT_FLOW_WINDOW, None);
+        Visitor::new(visitor_id.to_string(), &proxy_config, tx)
+    }
+
+    #[test]
+    fn insert_replaces_duplicate_visitor_id() {
+        let visitors = VisitorRegistry::new();
+        let old = visitors.insert(visitor("a", 9000));
+        let new = visitors.insert(visitor("a", 9001));
+        assert_eq!(visitors.len(), 1);
+        assert!(!visitors.contains(&old));
+        assert!(visitors.contains(&new));
+        assert_eq!(
+            visitors.get("a").map(|current| current.open_port()),
+            Some(9001)
+        );
+    }
+
+    #[test]
+    fn remove_if_keeps_replacing_visitor() {
+        let visitors = VisitorRegistry::new();
+        let old = visitors.insert(visitor("a", 9000));
+        let new = visitors.insert(visitor("a", 9000));
+        assert!(visitors.remove_if("a", &old).is_none());
+        assert!(visitors.contains(&new));
+        assert!(visitors.remove_if("a", &new).is_some());
+        assert!(visitors.is_empty());
+    }
+
+    #[test]
+    fn remove_port_closes_only_that_port() {
+        let visitors = VisitorRegistry::new();
+        for i in 0..10 {
+            visitors.insert(visitor(&format!("a{}", i), 9000));
+            visitors.insert(visitor(&format!("b{}", i), 9001));
+        }
+        assert_eq!(visitors.remove_port(9000), 10);
+        assert_eq!(visitors.remove_port(9000), 0);
+        assert_eq!(visitors.len(), 10);
+        assert!(visitors
+            .visitors()
+            .iter()
+            .all(|visitor| visitor.open_port() == 9001));
+    }
+
+    #[test]
+    fn len_and_drain_cover_all_shards() {
+        let visitors = VisitorRegistry::new();
+        let count = SHARDS * 4;
+        for i in 0..count {
+            visitors.insert(visitor(&i.to_string(), 9000));
+        }
+        assert_eq!(visitors.len(), count);
+        assert_eq!(visitors.visitors().len(), count);
+        assert_eq!(visitors.drain().len(), count);
+        assert!(visitors.is_empty());
+        assert_eq!(visitors.len(), 0);
+    }
+}
@@ -0,0 +1,84 @@
+use tokio::{
+    sync::{broadcast, mpsc},
+    time,
+};
+
+use crate::{
+    client::{
+        event::ClientEvent,
+        handle::ClientCommand,
+        session::{apply_offline_command, recv_command, run_session, SessionEnd},
+    },
+    common::error::{ClientError, Result},
+    config::{client::ClientConfig, log::MessageLogConfig},
+    net::{backoff::Backoff, tls::build_connector},
+};
+
+/// 客户端运行时: 维持与服务端的会话, 断线后按退避策略重连
+pub(crate) async fn run(
+    mut client_config: ClientConfig,
+    message_log: MessageLogConfig,
+    mut cmd_rx: mpsc::Receiver<ClientCommand>,
+    event_tx: broadcast::Sender<ClientEvent>,
+) -> Result<()> {
+    let tls_connector = build_connector(client_config.get_tls_config())?;
+    let mut backoff = Backoff::new(client_config.get_reconnect_config().clone());
+
+    let result = 'supervise: loop {
+        let reason = match run_session(
+            &mut client_config,
+            &mut backoff,
+            &mut cmd_rx,
+            &event_tx,
+            tls_connector.as_ref(),
+            &message_log,
+        )
+        .await
+        {
+            Ok(SessionEnd::Shutdown) => break Ok(()),
+            Ok(SessionEnd::AuthFailed(reason)) => {
+                log::error!("认证失败, 客户端退出");
+                break Err(ClientError::Auth(reason));
+            }
+            Ok(SessionEnd::ConnectionLost) => {
+                log::error!("与服务端的连接已断开");
+                String::from("与服务端的连接已断开")
+            }
+            Err(e) => {
+                log::error!("连接服务端失败: {}", e);
+                e.to_string()
+            }
+        };
+        let _ = event_tx.send(ClientEvent::Disconnected(reason));
+
+        let delay = match backoff.next_delay() {
+            Some(delay) => delay,
+            None => {
+                log::error!("已连续重连 {} 次仍未成功, 客户端退出", backoff.attempts());
+                break Err(ClientError::ReconnectExhausted(backoff.attempts()));
+            }
+        };
+        log::info!("{:?} 后进行第 {} 次重连", delay, backoff.attempts());
+        let _ = event_tx.send(ClientEvent::Reconnecting {
+            attempt: backoff.attempts(),
+            delay,
+        });
+
+        // 等待重连期间仍然响应指令
+        let sleep = time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                cmd = recv_command(&mut cmd_rx) => {
+                    if apply_offline_command(&mut client_config, cmd) {
+                        break 'supervise Ok(());
+                    }
+                }
+            }
+        }
+    };
+
+    let _ = event_tx.send(ClientEvent::Stopped);
+    result
+}
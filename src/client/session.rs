@@ -0,0 +1,880 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::{self, Duration, Instant, Interval, MissedTickBehavior},
+};
+use tokio_rustls::TlsConnector;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::{
+    client::{
+        event::ClientEvent,
+        flow::{self, DeliverError, Payload, MIN_FLOW_WINDOW},
+        handle::ClientCommand,
+        process::{process, ProcessOptions},
+        registry::{Visitor, VisitorRegistry},
+    },
+    common::error::{ClientError, Result},
+    config::{
+        client::ClientConfig,
+        log::MessageLogConfig,
+        validate::{validate_proxies, validate_proxy, ValidateOptions, ValidationReport},
+    },
+    core::transfer_message::TransferDataMessage,
+    helper::{
+        compress::DEFLATE,
+        message::{auth_proof, timestamp_skew},
+        redact::log_message,
+    },
+    model::{
+        message::{AuthMethod, ProtocolMessage},
+        proxy::{ProxyConfig, ProxyDiff},
+        tunnel::{TunnelState, TunnelTable},
+    },
+    net::{
+        backoff::Backoff, codec::TransferMessageCodec, heartbeat::RttTracker, transport,
+        writer::write_frames,
+    },
+};
+
+/// 认证挑战与本机时钟的偏差超过该值时提示检查系统时间
+const AUTH_MAX_SKEW: Duration = Duration::from_secs(300);
+
+/// 优雅退出时检查访问者是否已全部结束的间隔
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 服务端不支持流量控制时, 等待单个访问者的本地目标消费数据的最长时间
+const LEGACY_DELIVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 优雅退出时等待写任务发出剩余消息的时间; 另加 connectTimeout,
+/// 正在连接本地目标的访问者任务持有发送端, 最迟在连接超时后结束
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一次会话的结束原因
+pub(crate) enum SessionEnd {
+    /// 与服务端的连接断开, 需要重连
+    ConnectionLost,
+    /// 认证失败, 重连也无法恢复
+    AuthFailed(String),
+    /// 收到停止指令
+    Shutdown,
+}
+
+/// 建立一次与服务端的会话: 连接、认证、开放端口并处理服务端消息, 直到连接断开
+pub(crate) async fn run_session(
+    client_config: &mut ClientConfig,
+    backoff: &mut Backoff,
+    cmd_rx: &mut mpsc::Receiver<ClientCommand>,
+    event_tx: &broadcast::Sender<ClientEvent>,
+    tls_connector: Option<&TlsConnector>,
+    message_log: &MessageLogConfig,
+) -> Result<SessionEnd> {
+    // 建立连接, 期间仍然响应指令; 连接使用配置的副本, 以便指令修改代理列表
+    let server_config = client_config.clone();
+    let connect = transport::connect(&server_config, tls_connector);
+    tokio::pin!(connect);
+    let server_stream = loop {
+        tokio::select! {
+            server_stream = &mut connect => break server_stream?,
+            cmd = recv_command(cmd_rx) => {
+                if apply_offline_command(client_config, cmd) {
+                    log::info!("连接服务端期间收到停止指令, 不再连接");
+                    return Ok(SessionEnd::Shutdown);
+                }
+            }
+        }
+    };
+    let _ = event_tx.send(ClientEvent::Connected);
+    let (reader, writer) = tokio::io::split(server_stream);
+    let codec = TransferMessageCodec::with_max_frame_size(client_config.get_max_frame_size());
+    let mut reader = FramedRead::new(reader, codec.clone());
+    let writer = FramedWrite::new(writer, codec);
+    // 客户端向服务端写回数据时用到的channel
+    let (s_tx, s_rx) = mpsc::channel::<TransferDataMessage>(32);
+    // 客户端从服务端读取数据时用到的channel
+    let (r_tx, mut r_rx) = mpsc::channel::<TransferDataMessage>(32);
+    let heartbeat_config = client_config.get_heartbeat_config().clone();
+
+    // 接收消息 并合并写入服务端
+    let write_config = client_config.get_write_config().clone();
+    let writer_log = message_log.clone();
+    let writer_task = tokio::spawn(async move {
+        if let Err(e) = write_frames(writer, s_rx, write_config, writer_log).await {
+            log::error!("向服务端写入数据失败: {}", e);
+        }
+    });
+
+    // 读取数据 并发送到消费者, 超过读空闲时间未收到任何消息时判定连接已断开
+    let idle_timeout = heartbeat_config.get_idle_timeout();
+    let reader_log = message_log.clone();
+    let reader_task = tokio::spawn(async move {
+        loop {
+            let frame = match idle_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, reader.next()).await {
+                    Ok(frame) => frame,
+                    Err(_) => {
+                        log::error!("{:?} 内未收到服务端任何消息, 判定连接已断开", timeout);
+                        break;
+                    }
+                },
+                None => reader.next().await,
+            };
+            let server_rsp = match frame {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => {
+                    log::error!("从服务端读取数据失败: {}", e);
+                    break;
+                }
+                None => break,
+            };
+            log_message("response from server", &server_rsp, &reader_log);
+            if r_tx.send(server_rsp).await.is_err() {
+                break;
+            }
+        }
+    });
+    // 会话结束时终止读写任务
+    let mut guard = AbortOnDrop(vec![writer_task, reader_task]);
+
+    let auth_message = ProtocolMessage::Auth {
+        method: client_config.get_auth_method(),
+        flow_window: Some(client_config.get_flow_window()),
+        compression: Some(DEFLATE.to_string()),
+    };
+    if s_tx.send(auth_message.into()).await.is_err() {
+        return Ok(SessionEnd::ConnectionLost);
+    }
+
+    let mut session = Session {
+        client_config,
+        visitors: Arc::new(VisitorRegistry::new()),
+        backoff,
+        s_tx,
+        tunnels: TunnelTable::new(),
+        rtt: RttTracker::new(),
+        license_key: None,
+        peer_window: None,
+        compression: false,
+        challenge_answered: false,
+        stopping: false,
+        drain: None,
+        drain_deadline: None,
+        event_tx,
+    };
+
+    let mut heartbeat = heartbeat_config.get_interval().map(|period| {
+        let mut interval = time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    // 消费 生产者生产的数据, 并定时发送心跳
+    loop {
+        let result = tokio::select! {
+            msg = r_rx.recv() => match msg {
+                Some(msg) => session.handle_message(msg).await,
+                None => break,
+            },
+            _ = next_tick(&mut heartbeat) => session.send_heartbeat().await.map(|_| None),
+            cmd = recv_command(cmd_rx) => session.handle_command(cmd).await,
+            _ = next_tick(&mut session.drain) => session.check_drain().await,
+        };
+        match result {
+            Ok(Some(SessionEnd::Shutdown)) => {
+                // 释放会话持有的发送端, 等待写任务把剩余消息发出
+                drop(session);
+                let flush_timeout = client_config.get_connect_timeout() + FLUSH_TIMEOUT;
+                flush_writer(guard.0.swap_remove(0), flush_timeout).await;
+                return Ok(SessionEnd::Shutdown);
+            }
+            Ok(Some(end)) => return Ok(end),
+            Ok(None) => {}
+            // 写任务已退出, 连接不可用
+            Err(ClientError::ChannelClosed) => break,
+            // 单条消息处理失败不影响其他隧道
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    // 退出过程中连接断开, 无需再等待访问者结束, 也不再重连
+    if session.stopping {
+        log::info!("退出过程中与服务端的连接已断开, 会话结束");
+        return Ok(SessionEnd::Shutdown);
+    }
+    Ok(SessionEnd::ConnectionLost)
+}
+
+/// 单次会话的状态
+struct Session<'a> {
+    client_config: &'a mut ClientConfig,
+    /// 本次会话的访问者, 会话结束时全部关闭
+    visitors: Arc<VisitorRegistry>,
+    backoff: &'a mut Backoff,
+    s_tx: mpsc::Sender<TransferDataMessage>,
+    tunnels: TunnelTable,
+    rtt: RttTracker,
+    /// 认证通过后服务端分配的授权码
+    license_key: Option<String>,
+    /// 服务端通告的单个访问者接收窗口, None 表示服务端不支持流量控制
+    peer_window: Option<u32>,
+    /// 服务端是否同意压缩 TRANSFER 数据
+    compression: bool,
+    /// 每次会话只应答一次认证挑战
+    challenge_answered: bool,
+    /// 已收到停止指令, 此后连接断开时不再重连
+    stopping: bool,
+    /// 优雅退出中, 定时检查访问者是否已全部结束
+    drain: Option<Interval>,
+    /// 等待访问者结束的截止时间
+    drain_deadline: Option<Instant>,
+    event_tx: &'a broadcast::Sender<ClientEvent>,
+}
+
+/// 会话结束后访问者连接随之失效, 关闭对应的通道
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        let visitors = self.visitors.drain();
+        if !visitors.is_empty() {
+            log::info!("清理 {} 个失效的访问者通道", visitors.len());
+        }
+    }
+}
+
+impl Session<'_> {
+    fn emit(&self, event: ClientEvent) {
+        // 没有订阅方时发送失败, 忽略即可
+        let _ = self.event_tx.send(event);
+    }
+
+    /// 处理来自句柄的指令, 返回 Some 时结束会话
+    async fn handle_command(&mut self, cmd: ClientCommand) -> Result<Option<SessionEnd>> {
+        match cmd {
+            // 退出过程中不再开放新的端口
+            ClientCommand::AddProxy(_, reply) if self.stopping => {
+                let _ = reply.send(Err(ClientError::ShuttingDown));
+            }
+            ClientCommand::ReloadProxies(_, reply) if self.stopping => {
+                let _ = reply.send(Err(ClientError::ShuttingDown));
+            }
+            ClientCommand::AddProxy(proxy_config, reply) => {
+                let result = add_proxy(self.client_config, proxy_config.clone());
+                if result.is_ok() {
+                    self.open_tunnel(&proxy_config).await?;
+                }
+                let _ = reply.send(result);
+            }
+            ClientCommand::RemoveProxy(open_port, reply) => {
+                let result = remove_proxy(self.client_config, open_port);
+                let result = match result {
+                    Ok(proxy_config) => self.close_tunnel(proxy_config).await,
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+            ClientCommand::ReloadProxies(proxies, reply) => {
+                let result = reload_proxies(self.client_config, proxies);
+                if let Some(diff) = result.as_ref().ok().filter(|diff| !diff.is_empty()) {
+                    for proxy_config in &diff.removed {
+                        self.close_tunnel(proxy_config.clone()).await?;
+                    }
+                    for proxy_config in &diff.added {
+                        self.open_tunnel(proxy_config).await?;
+                    }
+                    log::info!(
+                        "代理列表已重新加载: 新增 {} 个, 移除 {} 个, 未变化 {} 个",
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.unchanged
+                    );
+                }
+                let _ = reply.send(result);
+            }
+            ClientCommand::Shutdown => {
+                // 退出过程中再次收到停止指令时不再等待
+                if self.drain.is_some() {
+                    return self.finish_shutdown().await.map(Some);
+                }
+                self.stopping = true;
+                return self.begin_shutdown().await;
+            }
+        }
+        Ok(None)
+    }
+
+    /// 开始优雅退出: 关闭所有隧道、不再接受新的访问者, 并等待现有访问者结束
+    async fn begin_shutdown(&mut self) -> Result<Option<SessionEnd>> {
+        if let Some(license_key) = self.license_key.clone() {
+            let proxies: Vec<ProxyConfig> = self
+                .tunnels
+                .iter()
+                .map(|(proxy_config, _)| proxy_config.clone())
+                .collect();
+            for proxy_config in proxies {
+                self.tunnels.close(proxy_config.open_port());
+                self.send(ProtocolMessage::close_server(
+                    &proxy_config,
+                    license_key.clone(),
+                ))
+                .await?;
+            }
+        }
+
+        let visitors = self.visitors.len();
+        let drain_timeout = match self.client_config.get_drain_timeout() {
+            Some(drain_timeout) if visitors > 0 => drain_timeout,
+            _ => return self.finish_shutdown().await.map(Some),
+        };
+        log::info!(
+            "等待 {} 个访问者连接结束, 最长 {:?}",
+            visitors,
+            drain_timeout
+        );
+        self.emit(ClientEvent::Draining(visitors));
+        let deadline = Instant::now() + drain_timeout;
+        let mut drain = time::interval(DRAIN_CHECK_INTERVAL);
+        drain.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        self.drain = Some(drain);
+        self.drain_deadline = Some(deadline);
+        Ok(None)
+    }
+
+    /// 访问者已全部结束或等待超时时结束会话
+    async fn check_drain(&mut self) -> Result<Option<SessionEnd>> {
+        let expired = self
+            .drain_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if expired || self.visitors.is_empty() {
+            return self.finish_shutdown().await.map(Some);
+        }
+        Ok(None)
+    }
+
+    /// 断开剩余的访问者连接, 结束会话
+    async fn finish_shutdown(&mut self) -> Result<SessionEnd> {
+        let visitors = self.visitors.drain();
+        if let Some(license_key) = self.license_key.clone() {
+            if !visitors.is_empty() {
+                log::warn!("断开 {} 个未结束的访问者连接", visitors.len());
+            }
+            for visitor in visitors {
+                self.send(ProtocolMessage::Disconnect {
+                    license_key: license_key.clone(),
+                    visitor_id: visitor.visitor_id().to_string(),
+                })
+                .await?;
+            }
+        }
+        log::info!("会话已结束");
+        Ok(SessionEnd::Shutdown)
+    }
+
+    /// 已认证时请求服务端开放端口, 未认证时等待认证通过后统一开放
+    async fn open_tunnel(&mut self, proxy_config: &ProxyConfig) -> Result<()> {
+        let license_key = match &self.license_key {
+            Some(license_key) => license_key.clone(),
+            None => return Ok(()),
+        };
+        self.send(ProtocolMessage::open_server(proxy_config, license_key))
+            .await?;
+        self.tunnels.request(proxy_config.clone());
+        Ok(())
+    }
+
+    /// 已认证时通知服务端关闭端口, 并关闭该端口下的访问者连接
+    async fn close_tunnel(&mut self, proxy_config: ProxyConfig) -> Result<()> {
+        if let Some(license_key) = self.license_key.clone() {
+            if self.tunnels.close(proxy_config.open_port()).is_some() {
+                self.send(ProtocolMessage::close_server(&proxy_config, license_key))
+                    .await?;
+            }
+        }
+        self.visitors.remove_port(proxy_config.open_port());
+        self.emit(ClientEvent::TunnelClosed(proxy_config));
+        Ok(())
+    }
+
+    /// 拒绝访问者的连接
+    async fn reject_visitor(&self, license_key: String, visitor_id: String) -> Result<()> {
+        self.send(ProtocolMessage::Disconnect {
+            license_key,
+            visitor_id,
+        })
+        .await
+    }
+
+    async fn send(&self, message: ProtocolMessage) -> Result<()> {
+        self.s_tx.send(message.into()).await?;
+        Ok(())
+    }
+
+    /// 处理一条服务端消息, 返回 Some 时结束会话
+    async fn handle_message(
+        &mut self,
+        server_rsp: TransferDataMessage,
+    ) -> Result<Option<SessionEnd>> {
+        match ProtocolMessage::try_from(server_rsp)? {
+            ProtocolMessage::Heartbeat {
+                heartbeat_id, ack, ..
+            } => self.handle_heartbeat(heartbeat_id, ack).await?,
+            ProtocolMessage::AuthChallenge { nonce, issued_at } => {
+                return self.handle_auth_challenge(nonce, issued_at).await;
+            }
+            ProtocolMessage::AuthOk {
+                license_key,
+                flow_window,
+                compression,
+            } => {
+                return self
+                    .handle_auth_ok(license_key, flow_window, compression)
+                    .await;
+            }
+            ProtocolMessage::AuthErr { reason } => {
+                let reason = reason.unwrap_or_else(|| String::from("密码错误"));
+                return Ok(Some(self.auth_failed(reason)));
+            }
+            ProtocolMessage::OpenServer {
+                open_port, reason, ..
+            } => {
+                // 服务端以 OPEN_SERVER 应答开放结果, 携带 message 时表示开放失败
+                let state = match reason {
+                    Some(reason) => TunnelState::Failed(reason),
+                    None => TunnelState::Live,
+                };
+                self.handle_server_reply(open_port, state);
+            }
+            ProtocolMessage::CloseServer {
+                open_port, reason, ..
+            } => {
+                if self.tunnels.take_close_ack(open_port) {
+                    log::info!("服务端已关闭端口 {}", open_port);
+                } else {
+                    // 服务端主动关闭了端口, 该端口下的访问者连接随之失效
+                    self.visitors.remove_port(open_port);
+                    let reason = reason.unwrap_or_else(|| String::from("服务端关闭了端口"));
+                    self.handle_server_reply(open_port, TunnelState::Failed(reason));
+                }
+            }
+            ProtocolMessage::Connect {
+                license_key,
+                visitor_id,
+                proxy,
+                ..
+            } => self.handle_connect(license_key, visitor_id, proxy).await?,
+            ProtocolMessage::Disconnect { visitor_id, .. } => {
+                log::error!("收到 disconnect 消息，visitor_id: {}", visitor_id);
+                // 移除并关闭对应的 sender，通知 process 内部任务退出
+                self.visitors.remove(&visitor_id);
+            }
+            ProtocolMessage::Transfer {
+                license_key,
+                visitor_id,
+                data,
+                compressed,
+            } => {
+                self.handle_transfer(license_key, visitor_id, Payload { data, compressed })
+                    .await?
+            }
+            ProtocolMessage::WindowUpdate {
+                visitor_id,
+                increment,
+                ..
+            } => match self.visitors.get(&visitor_id) {
+                Some(visitor) => visitor.sender().window().grant(increment),
+                // 访问者可能已先行关闭
+                None => log::debug!("忽略已关闭的 visitor_id {} 的窗口更新", visitor_id),
+            },
+            message @ ProtocolMessage::Auth { .. } => {
+                log::warn!("忽略未处理的 {:?} 消息", message.cmd_type());
+            }
+        }
+        Ok(None)
+    }
+
+    /// 定时发送心跳
+    async fn send_heartbeat(&mut self) -> Result<()> {
+        let (heartbeat_id, missed) = self.rtt.start();
+        if let Some(missed) = missed {
+            log::warn!("心跳 {} 未收到应答", missed);
+        }
+        self.send(ProtocolMessage::Heartbeat {
+            heartbeat_id: Some(heartbeat_id),
+            license_key: self.license_key.clone(),
+            ack: false,
+        })
+        .await
+    }
+
+    /// 心跳可能早于认证到达, 不携带授权码
+    async fn handle_heartbeat(&mut self, heartbeat_id: Option<u64>, ack: bool) -> Result<()> {
+        // 旧版服务端原样回送心跳而不携带 heartbeat_ack, 只能按等待中的序号识别;
+        // 本端序号从随机值开始, 不会与服务端主动发起的心跳序号相撞
+        if ack || heartbeat_id.is_some_and(|id| self.rtt.is_pending(id)) {
+            // 服务端对本端心跳的应答
+            match heartbeat_id.and_then(|id| self.rtt.complete(id)) {
+                Some(sample) => log::info!(
+                    "心跳 RTT: {:?}, 平滑 RTT: {:?}",
+                    sample,
+                    self.rtt.smoothed().unwrap_or(sample)
+                ),
+                None => log::debug!("忽略过期的心跳应答: {:?}", heartbeat_id),
+            }
+            return Ok(());
+        }
+        self.send(ProtocolMessage::Heartbeat {
+            heartbeat_id,
+            license_key: self.license_key.clone(),
+            ack: true,
+        })
+        .await
+    }
+
+    /// 以密码计算挑战应答, 不在网络上发送密码本身
+    async fn handle_auth_challenge(
+        &mut self,
+        nonce: String,
+        issued_at: Option<prost_types::Timestamp>,
+    ) -> Result<Option<SessionEnd>> {
+        if self.challenge_answered {
+            return Ok(Some(self.auth_failed(String::from("收到重复的认证挑战"))));
+        }
+        let password = match self.client_config.get_password() {
+            Some(password) => password.to_string(),
+            None => {
+                let reason = String::from("未配置 password, 无法应答认证挑战");
+                return Ok(Some(self.auth_failed(reason)));
+            }
+        };
+        // 过期与重放由服务端校验应答中的时间戳拒绝, 客户端只提示时钟偏差
+        if let Some(skew) = issued_at
+            .and_then(|issued_at| timestamp_skew(&issued_at))
+            .filter(|skew| *skew > AUTH_MAX_SKEW)
+        {
+            log::warn!("认证挑战的时间与本机时钟相差 {:?}, 请检查系统时间", skew);
+        }
+        if nonce.is_empty() {
+            return Ok(Some(self.auth_failed(String::from("认证挑战的随机数为空"))));
+        }
+
+        let timestamp = prost_types::Timestamp::from(std::time::SystemTime::now());
+        let proof = auth_proof(&password, &nonce, &timestamp);
+        self.challenge_answered = true;
+        self.send(ProtocolMessage::Auth {
+            method: AuthMethod::Response {
+                nonce,
+                proof,
+                timestamp,
+            },
+            flow_window: Some(self.client_config.get_flow_window()),
+            compression: Some(DEFLATE.to_string()),
+        })
+        .await?;
+        Ok(None)
+    }
+
+    /// 认证失败, 结束会话且不再重连
+    fn auth_failed(&self, reason: String) -> SessionEnd {
+        self.emit(ClientEvent::AuthFailed(reason.clone()));
+        SessionEnd::AuthFailed(reason)
+    }
+
+    /// 认证通过, 为每个代理配置请求开放端口; 服务端的协商参数无法使用时结束会话
+    async fn handle_auth_ok(
+        &mut self,
+        license_key: String,
+        flow_window: Option<u32>,
+        compression: Option<String>,
+    ) -> Result<Option<SessionEnd>> {
+        log::info!("认证通过");
+        if let Some(flow_window) = flow_window.filter(|window| *window < MIN_FLOW_WINDOW) {
+            let reason = format!(
+                "服务端通告的流量控制窗口 {} 字节过小, 至少需要 {} 字节",
+                flow_window, MIN_FLOW_WINDOW
+            );
+            log::error!("{}", reason);
+            return Ok(Some(self.auth_failed(reason)));
+        }
+        match flow_window {
+            Some(flow_window) => log::info!("服务端支持流量控制, 窗口 {} 字节", flow_window),
+            None => log::info!("服务端不支持流量控制, 接收窗口仅作为缓冲上限"),
+        }
+        self.compression = compression.as_deref() == Some(DEFLATE);
+        match compression {
+            Some(compression) if self.compression => {
+                log::info!("服务端支持 {} 压缩", compression)
+            }
+            Some(compression) => {
+                log::warn!("服务端选择了不支持的压缩算法 {}, 不压缩数据", compression)
+            }
+            None => log::info!("服务端不支持压缩, 不压缩数据"),
+        }
+        self.backoff.reset();
+        self.license_key = Some(license_key);
+        self.peer_window = flow_window;
+        self.emit(ClientEvent::Authenticated);
+        if self.client_config.get_proxy().is_empty() {
+            log::warn!("未配置任何代理, 不会开启隧道");
+        }
+        for proxy_config in self.client_config.get_proxy().clone() {
+            self.open_tunnel(&proxy_config).await?;
+        }
+        Ok(None)
+    }
+
+    /// 记录服务端对代理端口的应答
+    fn handle_server_reply(&mut self, open_port: i32, state: TunnelState) {
+        let proxy_config = match self.tunnels.update(open_port, state.clone()) {
+            Some(proxy_config) => proxy_config.clone(),
+            None => {
+                log::warn!("收到未请求端口 {} 的应答", open_port);
+                return;
+            }
+        };
+        match state {
+            TunnelState::Live => self.emit(ClientEvent::TunnelOpened(proxy_config)),
+            TunnelState::Failed(reason) => self.emit(ClientEvent::TunnelFailed {
+                proxy: proxy_config,
+                reason,
+            }),
+            TunnelState::Pending => {}
+        }
+        if self.tunnels.is_settled() {
+            self.tunnels.report();
+        }
+    }
+
+    /// 服务端通知有新的访问者, 建立与本地目标的连接
+    async fn handle_connect(
+        &mut self,
+        license_key: String,
+        visitor_id: String,
+        server_proxy: ProxyConfig,
+    ) -> Result<()> {
+        // 优雅退出中不再接受新的访问者
+        if self.drain.is_some() {
+            log::info!("正在退出, 拒绝 visitor_id {} 的连接", visitor_id);
+            return self.reject_visitor(license_key, visitor_id).await;
+        }
+        // 目标、协议、压缩与加密都以本地配置为准, 服务端只能选择本端已请求开放的隧道;
+        // 否则服务端可以借未配置或已关闭的端口绕过端到端加密, 或让客户端连接任意地址
+        let open_port = server_proxy.open_port();
+        let proxy_config = match self.tunnels.get(open_port) {
+            Some(local) => local.clone(),
+            None => {
+                log::warn!(
+                    "端口 {} 未开放或已关闭, 拒绝 visitor_id {} 的连接",
+                    open_port,
+                    visitor_id
+                );
+                return self.reject_visitor(license_key, visitor_id).await;
+            }
+        };
+        if proxy_config.host() != server_proxy.host()
+            || proxy_config.port() != server_proxy.port()
+            || proxy_config.protocol() != server_proxy.protocol()
+        {
+            log::warn!(
+                "服务端为端口 {} 下发的目标 {}://{}:{} 与本地配置不一致, 以本地配置为准",
+                open_port,
+                server_proxy.protocol().as_str(),
+                server_proxy.host(),
+                server_proxy.port()
+            );
+        }
+        // 创建一个新的 channel 用于与 process 任务通信, 缓冲由流量控制窗口限制
+        let (p_tx, p_rx) = flow::channel(self.client_config.get_flow_window(), self.peer_window);
+        let visitor = Arc::downgrade(&self.visitors.insert(Visitor::new(
+            visitor_id.clone(),
+            &proxy_config,
+            p_tx,
+        )));
+
+        // 在独立任务中连接本地目标, 缓慢或无响应的目标不阻塞其他访问者的数据分发
+        let s_tx = self.s_tx.clone();
+        let visitors = self.visitors.clone();
+        let mut options = ProcessOptions::from(&*self.client_config);
+        // 服务端同意压缩且本地为该端口开启了压缩时才压缩发出的数据
+        if self.compression && proxy_config.is_compress() {
+            options.compressor = Some(self.client_config.get_compression_config().compressor());
+        }
+        options.e2e_key = proxy_config.e2e_key().cloned();
+        tokio::spawn(async move {
+            if let Err(e) = process(
+                proxy_config,
+                license_key,
+                visitor.clone(),
+                p_rx,
+                s_tx,
+                visitors.clone(),
+                options,
+            )
+            .await
+            {
+                if let Some(visitor) = visitor.upgrade() {
+                    visitors.remove_if(&visitor_id, &visitor);
+                }
+                log::error!("{}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// 将服务端转发的数据交给对应访问者的本地连接, 单个访问者最多阻塞分发循环 `LEGACY_DELIVER_TIMEOUT`
+    async fn handle_transfer(
+        &mut self,
+        license_key: String,
+        visitor_id: String,
+        payload: Payload,
+    ) -> Result<()> {
+        // 本地连接可能已先行关闭, 服务端收到 DISCONNECT 前发出的残留数据直接丢弃
+        let visitor = match self.visitors.get(&visitor_id) {
+            Some(visitor) => visitor,
+            None => {
+                log::debug!("visitor_id {} 已关闭, 丢弃残留数据", visitor_id);
+                return Ok(());
+            }
+        };
+        let n = payload.data.len();
+        let sender = visitor.sender();
+        let delivered = if sender.window().is_negotiated() {
+            sender.deliver(payload)
+        } else {
+            // 服务端不支持流量控制, 只能等待本地目标消费, 对整个连接施加背压;
+            // 本地连接尚未建立时不等待, 避免缓慢的目标拖住其他访问者和心跳
+            let wait = if visitor.is_connected() {
+                LEGACY_DELIVER_TIMEOUT
+            } else {
+                Duration::ZERO
+            };
+            sender.deliver_wait(payload, wait).await
+        };
+        match delivered {
+            Ok(()) => {
+                visitor.record_in(n);
+                Ok(())
+            }
+            Err(DeliverError::Closed) => {
+                log::debug!("visitor_id {} 已关闭, 丢弃残留数据", visitor_id);
+                Ok(())
+            }
+            Err(DeliverError::WindowExceeded) => {
+                // 服务端未遵守接收窗口, 只断开该访问者
+                log::warn!("visitor_id {} 的数据超出接收窗口, 断开连接", visitor_id);
+                self.visitors.remove_if(&visitor_id, &visitor);
+                self.reject_visitor(license_key, visitor_id).await
+            }
+            Err(DeliverError::Stalled) => {
+                // 本地目标长时间不消费数据, 只断开该访问者, 不再阻塞整个连接
+                log::warn!(
+                    "visitor_id {} 的本地目标 {} 未能及时消费数据, 断开连接",
+                    visitor_id,
+                    visitor.target()
+                );
+                self.visitors.remove_if(&visitor_id, &visitor);
+                self.reject_visitor(license_key, visitor_id).await
+            }
+        }
+    }
+}
+
+/// 等待下一条指令; 句柄全部丢弃后永不返回
+pub(crate) async fn recv_command(cmd_rx: &mut mpsc::Receiver<ClientCommand>) -> ClientCommand {
+    match cmd_rx.recv().await {
+        Some(cmd) => cmd,
+        None => std::future::pending().await,
+    }
+}
+
+/// 未与服务端建立会话时处理指令, 只修改配置; 返回 true 表示收到停止指令
+pub(crate) fn apply_offline_command(client_config: &mut ClientConfig, cmd: ClientCommand) -> bool {
+    match cmd {
+        ClientCommand::AddProxy(proxy_config, reply) => {
+            let _ = reply.send(add_proxy(client_config, proxy_config));
+        }
+        ClientCommand::RemoveProxy(open_port, reply) => {
+            let result = remove_proxy(client_config, open_port).map(|_| ());
+            let _ = reply.send(result);
+        }
+        ClientCommand::ReloadProxies(proxies, reply) => {
+            let _ = reply.send(reload_proxies(client_config, proxies));
+        }
+        ClientCommand::Shutdown => return true,
+    }
+    false
+}
+
+/// 校验后将代理加入配置, 开放端口不允许重复
+pub(crate) fn add_proxy(client_config: &mut ClientConfig, proxy_config: ProxyConfig) -> Result<()> {
+    let mut report = ValidationReport::new();
+    validate_proxy(
+        "proxy",
+        &proxy_config,
+        ValidateOptions::default(),
+        &mut report,
+    );
+    let open_port = proxy_config.open_port();
+    if client_config
+        .get_proxy()
+        .iter()
+        .any(|proxy| proxy.open_port() == open_port)
+    {
+        report.push("proxy.openPort", format!("开放端口 {} 已存在", open_port));
+    }
+    report.into_result()?;
+    client_config.add_proxy(proxy_config);
+    Ok(())
+}
+
+/// 校验新的代理列表并替换配置, 返回与旧列表的差异
+pub(crate) fn reload_proxies(
+    client_config: &mut ClientConfig,
+    proxies: Vec<ProxyConfig>,
+) -> Result<ProxyDiff> {
+    let mut report = ValidationReport::new();
+    validate_proxies("proxies", &proxies, ValidateOptions::default(), &mut report);
+    report.into_result()?;
+    let diff = ProxyDiff::between(client_config.get_proxy(), &proxies);
+    client_config.set_proxies(proxies);
+    Ok(diff)
+}
+
+/// 按开放端口从配置中移除代理
+pub(crate) fn remove_proxy(
+    client_config: &mut ClientConfig,
+    open_port: i32,
+) -> Result<ProxyConfig> {
+    client_config
+        .remove_proxy(open_port)
+        .ok_or_else(|| ClientError::Config(format!("开放端口 {} 不存在", open_port)))
+}
+
+/// 等待定时器的下一次触发; 未设置定时器时永不返回
+async fn next_tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// 在离开作用域时终止持有的任务
+struct AbortOnDrop(Vec<JoinHandle<()>>);
+
+/// 等待写任务发出剩余消息, 超时后终止
+async fn flush_writer(mut writer_task: JoinHandle<()>, timeout: Duration) {
+    if time::timeout(timeout, &mut writer_task).await.is_err() {
+        log::warn!("等待剩余消息发送超时");
+        writer_task.abort();
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
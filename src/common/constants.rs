@@ -2,6 +2,18 @@
  * 认证密码
  */
 pub const AUTH_PASSWORD: &str = "auth_password";
+/**
+ * 认证方式
+ */
+pub const AUTH_METHOD: &str = "auth_method";
+/**
+ * 认证挑战随机数
+ */
+pub const AUTH_NONCE: &str = "auth_nonce";
+/**
+ * 认证挑战应答
+ */
+pub const AUTH_PROOF: &str = "auth_proof";
 /**
  * 代理主机
  */
@@ -14,6 +26,10 @@ pub const PROXY_PORT: &str = "proxy_port";
  * 代理协议
  */
 pub const PROXY_PROTOCOL: &str = "proxy_protocol";
+/**
+ * 代理是否压缩数据
+ */
+pub const PROXY_COMPRESS: &str = "proxy_compress";
 /**
  * 开放端口
  */
@@ -30,3 +46,31 @@ pub const VISITOR_ID: &str = "visitor_id";
  * 消息
  */
 pub const MESSAGE: &str = "message";
+/**
+ * 心跳序号
+ */
+pub const HEARTBEAT_ID: &str = "heartbeat_id";
+/**
+ * 心跳应答标记
+ */
+pub const HEARTBEAT_ACK: &str = "heartbeat_ack";
+/**
+ * 单个访问者的流量控制窗口
+ */
+pub const FLOW_WINDOW: &str = "flow_window";
+/**
+ * 流量控制额度增量
+ */
+pub const WINDOW_INCREMENT: &str = "window_increment";
+/**
+ * 数据压缩算法
+ */
+pub const COMPRESSION: &str = "compression";
+/**
+ * 数据已压缩标记
+ */
+pub const COMPRESSED: &str = "compressed";
+/**
+ * 端到端加密中本端为访问者选择的随机盐
+ */
+pub const E2E_SALT: &str = "e2e_salt";
@@ -0,0 +1,113 @@
+use std::{fmt, io};
+
+use tokio::{sync::mpsc::error::SendError, task::JoinError};
+
+use crate::{config::validate::ValidationReport, core::cmd_type::CmdType};
+
+/// 客户端错误
+#[derive(Debug)]
+pub enum ClientError {
+    /// 服务端消息不符合协议约定
+    Protocol(String),
+    /// 消息缺少必需的元数据
+    MissingMetadata {
+        cmd_type: CmdType,
+        key: &'static str,
+    },
+    /// 认证失败
+    Auth(String),
+    /// 网络读写失败
+    Io(io::Error),
+    /// TLS 配置或握手失败
+    Tls(String),
+    /// 配置错误
+    Config(String),
+    /// 配置校验未通过, 包含全部问题
+    Validation(ValidationReport),
+    /// 内部通道已关闭, 通常意味着会话已结束
+    ChannelClosed,
+    /// 连续重连失败次数达到上限
+    ReconnectExhausted(u32),
+    /// 客户端已退出, 退出结果已由之前的等待返回
+    AlreadyExited,
+    /// 客户端正在优雅退出, 不再接受代理变更
+    ShuttingDown,
+    /// 客户端任务 panic 或被取消
+    TaskFailed(JoinError),
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+impl ClientError {
+    /// 进程退出码, 参照 sysexits.h
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            // EX_CONFIG
+            ClientError::Config(_) | ClientError::Validation(_) => 78,
+            // EX_NOPERM
+            ClientError::Auth(_) => 77,
+            // EX_UNAVAILABLE
+            ClientError::ReconnectExhausted(_) => 69,
+            // EX_SOFTWARE
+            ClientError::TaskFailed(_) => 70,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Protocol(msg) => write!(f, "协议错误: {}", msg),
+            ClientError::MissingMetadata { cmd_type, key } => {
+                write!(f, "{} 消息缺少元数据 {}", cmd_type.as_str_name(), key)
+            }
+            ClientError::Auth(msg) => write!(f, "认证失败: {}", msg),
+            ClientError::Io(e) => write!(f, "IO 错误: {}", e),
+            ClientError::Tls(msg) => write!(f, "TLS 错误: {}", msg),
+            ClientError::Config(msg) => write!(f, "配置错误: {}", msg),
+            ClientError::Validation(report) => write!(f, "{}", report),
+            ClientError::ChannelClosed => write!(f, "通道已关闭"),
+            ClientError::ReconnectExhausted(attempts) => {
+                write!(f, "已连续重连 {} 次仍未成功", attempts)
+            }
+            ClientError::AlreadyExited => write!(f, "客户端已退出, 退出结果已返回"),
+            ClientError::ShuttingDown => write!(f, "客户端正在退出, 不再接受代理变更"),
+            ClientError::TaskFailed(e) => write!(f, "客户端任务异常退出: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(e) => Some(e),
+            ClientError::TaskFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<prost::DecodeError> for ClientError {
+    fn from(e: prost::DecodeError) -> Self {
+        ClientError::Protocol(e.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for ClientError {
+    fn from(e: serde_yaml::Error) -> Self {
+        ClientError::Config(e.to_string())
+    }
+}
+
+impl<T> From<SendError<T>> for ClientError {
+    fn from(_: SendError<T>) -> Self {
+        ClientError::ChannelClosed
+    }
+}
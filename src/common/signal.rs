@@ -0,0 +1,86 @@
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// 注册 unix 信号, 失败时只记录日志
+#[cfg(unix)]
+fn register(kind: SignalKind, name: &str) -> Option<Signal> {
+    signal(kind)
+        .map_err(|e| log::warn!("注册 {} 失败: {}", name, e))
+        .ok()
+}
+
+/// 等待信号; 未注册成功时永不返回
+#[cfg(unix)]
+async fn recv(signal: &mut Option<Signal>) {
+    if let Some(signal) = signal {
+        if signal.recv().await.is_some() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// SIGHUP 信号, 用于重新加载配置; 非 unix 平台永不触发
+pub struct Hangup {
+    #[cfg(unix)]
+    signal: Option<Signal>,
+}
+
+impl Hangup {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(unix)]
+            signal: register(SignalKind::hangup(), "SIGHUP"),
+        }
+    }
+
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        recv(&mut self.signal).await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+impl Default for Hangup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 退出信号: Ctrl-C(SIGINT), unix 平台上还包括 SIGTERM
+pub struct Terminate {
+    #[cfg(unix)]
+    signal: Option<Signal>,
+}
+
+impl Terminate {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(unix)]
+            signal: register(SignalKind::terminate(), "SIGTERM"),
+        }
+    }
+
+    /// 等待下一个退出信号, 返回信号名称
+    pub async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = recv(&mut self.signal) => "SIGTERM",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "Ctrl-C"
+        }
+    }
+}
+
+impl Default for Terminate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,21 +2,69 @@ use std::str;
 
 use clap::Parser;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// config file path
+    /// config file path, 不指定时仅从环境变量与命令行读取配置
     #[arg(short, long)]
-    config: String,
+    config: Option<String>,
+    /// .env file path, 默认读取当前目录下的 .env
+    #[arg(long)]
+    env_file: Option<String>,
+    /// 覆盖 client.serverHost
+    #[arg(long)]
+    server_host: Option<String>,
+    /// 覆盖 client.serverPort
+    #[arg(long)]
+    server_port: Option<String>,
+    /// 覆盖 client.password
+    #[arg(long)]
+    password: Option<String>,
+    /// 按路径覆盖任意配置项, 如 `--set client.tls.enabled=true`, 可重复指定
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    overrides: Vec<String>,
+    /// 校验配置(包括解析主机名)后退出, 不启动客户端
+    #[arg(long)]
+    check_config: bool,
 }
 
 impl Args {
-    pub fn get_config_path(&self) -> &str {
-        &self.config
+    pub fn get_config_path(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    pub fn is_check_config(&self) -> bool {
+        self.check_config
+    }
+
+    pub fn get_env_file(&self) -> Option<&str> {
+        self.env_file.as_deref()
+    }
+
+    /// 命令行中的配置覆盖项, 按 (路径, 值) 给出, 专用参数排在 `--set` 之后以优先生效
+    pub fn get_overrides(&self) -> Vec<(String, String)> {
+        let mut overrides: Vec<(String, String)> = self
+            .overrides
+            .iter()
+            .map(|entry| match entry.split_once('=') {
+                Some((path, value)) => (path.trim().to_string(), value.to_string()),
+                None => (entry.trim().to_string(), String::new()),
+            })
+            .collect();
+        let flags = [
+            ("client.serverHost", &self.server_host),
+            ("client.serverPort", &self.server_port),
+            ("client.password", &self.password),
+        ];
+        for (path, value) in flags {
+            if let Some(value) = value {
+                overrides.push((path.to_string(), value.clone()));
+            }
+        }
+        overrides
     }
 }
 
 pub fn get_args() -> Args {
-    let args = Args::parse();
-    return args;
+    Args::parse()
 }
@@ -1,10 +1,24 @@
 use serde::Deserialize;
-use std::error::Error;
-use std::fs;
+use serde_yaml::Value;
+use std::time::Duration;
+use std::{fs, io};
 
+use crate::client::flow::DEFAULT_FLOW_WINDOW;
+use crate::common::error::{ClientError, Result};
+
+use crate::model::message::AuthMethod;
 use crate::model::proxy::ProxyConfig;
+use crate::net::codec::DEFAULT_MAX_FRAME_SIZE;
 
+use super::arg::Args;
+use super::compression::CompressionConfig;
+use super::env::{apply_env, parse_value, set_path};
+use super::heartbeat::HeartbeatConfig;
 use super::log::LogConfig;
+use super::reconnect::ReconnectConfig;
+use super::tls::TlsConfig;
+use super::validate::{validate_client, validate_config, ValidateOptions, ValidationReport};
+use super::write::WriteConfig;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ClientConfig {
@@ -13,7 +27,73 @@ pub struct ClientConfig {
     server_host: String,
     #[serde(rename = "serverPort")]
     server_port: i32,
-    password: String,
+    /// 认证密码; 配置了客户端证书时可省略, 仅以证书认证
+    #[serde(default)]
+    password: Option<String>,
+    /// 明文发送密码的旧版认证, 仅在服务端不支持挑战应答时开启
+    #[serde(rename = "legacyAuth", default)]
+    legacy_auth: bool,
+    #[serde(rename = "maxFrameSize", default = "default_max_frame_size")]
+    max_frame_size: usize,
+    #[serde(default)]
+    reconnect: ReconnectConfig,
+    #[serde(default)]
+    heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    write: WriteConfig,
+    #[serde(default)]
+    compression: CompressionConfig,
+    /// UDP 会话空闲超时(毫秒)
+    #[serde(rename = "udpIdleTimeout", default = "default_udp_idle_timeout")]
+    udp_idle_timeout: u64,
+    /// 连接本地目标的超时(毫秒)
+    #[serde(rename = "connectTimeout", default = "default_connect_timeout")]
+    connect_timeout: u64,
+    /// 连接服务端(含 TLS 握手)的超时(毫秒)
+    #[serde(
+        rename = "serverConnectTimeout",
+        default = "default_server_connect_timeout"
+    )]
+    server_connect_timeout: u64,
+    #[serde(default)]
+    tls: TlsConfig,
+    /// 检查配置文件是否变化的间隔(毫秒), 0 表示只在收到 SIGHUP 时重新加载
+    #[serde(rename = "reloadInterval", default = "default_reload_interval")]
+    reload_interval: u64,
+    /// 退出时等待访问者连接结束的最长时间(毫秒), 0 表示立即断开
+    #[serde(rename = "drainTimeout", default = "default_drain_timeout")]
+    drain_timeout: u64,
+    /// 单个访问者的接收窗口(字节), 服务端支持时据此进行流量控制
+    #[serde(rename = "flowWindow", default = "default_flow_window")]
+    flow_window: u32,
+}
+
+fn default_max_frame_size() -> usize {
+    DEFAULT_MAX_FRAME_SIZE
+}
+
+fn default_udp_idle_timeout() -> u64 {
+    60_000
+}
+
+fn default_connect_timeout() -> u64 {
+    10_000
+}
+
+fn default_server_connect_timeout() -> u64 {
+    10_000
+}
+
+fn default_reload_interval() -> u64 {
+    5000
+}
+
+fn default_drain_timeout() -> u64 {
+    10_000
+}
+
+fn default_flow_window() -> u32 {
+    DEFAULT_FLOW_WINDOW
 }
 
 impl ClientConfig {
@@ -22,7 +102,20 @@ impl ClientConfig {
             proxies: Vec::new(),
             server_host,
             server_port,
-            password,
+            password: Some(password),
+            legacy_auth: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            reconnect: ReconnectConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            write: WriteConfig::default(),
+            compression: CompressionConfig::default(),
+            udp_idle_timeout: default_udp_idle_timeout(),
+            connect_timeout: default_connect_timeout(),
+            server_connect_timeout: default_server_connect_timeout(),
+            tls: TlsConfig::default(),
+            reload_interval: default_reload_interval(),
+            drain_timeout: default_drain_timeout(),
+            flow_window: default_flow_window(),
         }
     }
 
@@ -30,6 +123,20 @@ impl ClientConfig {
         self.proxies.push(proxy);
     }
 
+    /// 按服务端开放端口移除代理
+    pub fn remove_proxy(&mut self, open_port: i32) -> Option<ProxyConfig> {
+        let index = self
+            .proxies
+            .iter()
+            .position(|proxy| proxy.open_port() == open_port)?;
+        Some(self.proxies.remove(index))
+    }
+
+    /// 整体替换代理列表
+    pub fn set_proxies(&mut self, proxies: Vec<ProxyConfig>) {
+        self.proxies = proxies;
+    }
+
     pub fn get_proxy(&self) -> &Vec<ProxyConfig> {
         &self.proxies
     }
@@ -42,8 +149,89 @@ impl ClientConfig {
         self.server_port
     }
 
-    pub fn get_password(&self) -> &str {
-        &self.password
+    pub fn get_password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    pub fn is_legacy_auth(&self) -> bool {
+        self.legacy_auth
+    }
+
+    /// 开启或关闭明文密码认证
+    pub fn set_legacy_auth(&mut self, legacy_auth: bool) {
+        self.legacy_auth = legacy_auth;
+    }
+
+    /// 连接建立后首条认证消息的认证方式: 配置密码时默认走挑战应答, 未配置密码时使用客户端证书认证
+    pub fn get_auth_method(&self) -> AuthMethod {
+        match &self.password {
+            Some(password) if self.legacy_auth => AuthMethod::Password(password.clone()),
+            Some(_) => AuthMethod::Challenge,
+            None => AuthMethod::Certificate,
+        }
+    }
+
+    /// 启动前校验配置, 汇总全部问题; 不解析主机名
+    pub fn validate(&self) -> Result<()> {
+        let mut report = ValidationReport::new();
+        validate_client(self, ValidateOptions::default(), &mut report);
+        report.into_result()
+    }
+
+    pub fn get_max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    pub fn get_reconnect_config(&self) -> &ReconnectConfig {
+        &self.reconnect
+    }
+
+    pub fn get_heartbeat_config(&self) -> &HeartbeatConfig {
+        &self.heartbeat
+    }
+
+    pub fn get_write_config(&self) -> &WriteConfig {
+        &self.write
+    }
+
+    pub fn get_compression_config(&self) -> &CompressionConfig {
+        &self.compression
+    }
+
+    pub fn get_udp_idle_timeout(&self) -> Duration {
+        Duration::from_millis(self.udp_idle_timeout)
+    }
+
+    pub fn get_connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout)
+    }
+
+    pub fn get_server_connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.server_connect_timeout)
+    }
+
+    pub fn get_tls_config(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn get_flow_window(&self) -> u32 {
+        self.flow_window
+    }
+
+    /// 退出时等待访问者连接结束的最长时间, None 表示立即断开
+    pub fn get_drain_timeout(&self) -> Option<Duration> {
+        match self.drain_timeout {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// 检查配置文件是否变化的间隔, None 表示不检查
+    pub fn get_reload_interval(&self) -> Option<Duration> {
+        match self.reload_interval {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
     }
 }
 
@@ -65,12 +253,58 @@ impl ConfigWrapper {
     pub fn get_log_config(&self) -> &LogConfig {
         &self.log
     }
+
+    /// 校验全部配置, 汇总全部问题
+    pub fn validate(&self, options: ValidateOptions) -> Result<()> {
+        validate_config(self, options).into_result()
+    }
+}
+
+/// 读取并校验配置
+pub fn get_config(args: &Args) -> Result<ConfigWrapper> {
+    let config_wrapper = load_config(args)?;
+    config_wrapper.validate(ValidateOptions::default())?;
+    Ok(config_wrapper)
 }
 
-pub fn get_config(file_path: &str) -> Result<ConfigWrapper, Box<dyn Error>> {
-    // 读取文件内容
-    let yaml_content = fs::read_to_string(file_path)?;
-    // 反序列化 YAML 内容
-    let config_wrapper: ConfigWrapper = serde_yaml::from_str(&yaml_content)?;
+/// 分层读取配置: 默认值、YAML 文件、.env、`LDD_` 前缀的环境变量、命令行参数, 后者覆盖前者
+pub fn load_config(args: &Args) -> Result<ConfigWrapper> {
+    let mut root = match args.get_config_path() {
+        Some(file_path) => {
+            // 读取文件内容
+            let yaml_content = fs::read_to_string(file_path).map_err(|e| {
+                ClientError::Config(format!("读取配置文件 {} 失败: {}", file_path, e))
+            })?;
+            serde_yaml::from_str(&yaml_content)?
+        }
+        None => Value::Null,
+    };
+
+    // .env 中的变量不会覆盖已存在的环境变量
+    let dotenv_result = match args.get_env_file() {
+        Some(env_file) => dotenv::from_path(env_file).map_err(|e| (env_file, e)),
+        None => match dotenv::dotenv() {
+            Err(dotenv::Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map(|_| ()).map_err(|e| (".env", e)),
+        },
+    };
+    if let Err((env_file, e)) = dotenv_result {
+        return Err(ClientError::Config(format!(
+            "读取 {} 失败: {}",
+            env_file, e
+        )));
+    }
+    apply_env(&mut root, std::env::vars())?;
+
+    for (path, value) in args.get_overrides() {
+        if path.is_empty() {
+            return Err(ClientError::Config(String::from("--set 缺少配置路径")));
+        }
+        set_path(&mut root, &path, parse_value(&path, &value))?;
+    }
+
+    // 经文本中转, 使命令行与环境变量中的标量按目标字段类型解析
+    let merged = serde_yaml::to_string(&root)?;
+    let config_wrapper: ConfigWrapper = serde_yaml::from_str(&merged)?;
     Ok(config_wrapper)
 }
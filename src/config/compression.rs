@@ -0,0 +1,45 @@
+use serde::Deserialize;
+
+use crate::helper::compress::Compressor;
+
+/// TRANSFER 数据压缩配置, 只对配置了 `compress: true` 的代理生效
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompressionConfig {
+    /// 压缩级别, 取值 0-9
+    #[serde(default = "default_level")]
+    level: u32,
+    /// 短于该长度(字节)的数据不压缩
+    #[serde(rename = "minSize", default = "default_min_size")]
+    min_size: usize,
+}
+
+fn default_level() -> u32 {
+    6
+}
+
+fn default_min_size() -> usize {
+    512
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            min_size: default_min_size(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn get_min_size(&self) -> usize {
+        self.min_size
+    }
+
+    pub fn compressor(&self) -> Compressor {
+        Compressor::new(self.level, self.min_size)
+    }
+}
@@ -0,0 +1,287 @@
+use serde_yaml::{Mapping, Value};
+
+use crate::common::error::{ClientError, Result};
+
+/// 环境变量前缀
+pub const ENV_PREFIX: &str = "LDD_";
+
+/// 可由环境变量覆盖的配置项, 变量名由路径转换而来, 如 `client.serverHost` 对应 `LDD_CLIENT_SERVER_HOST`
+const ENV_PATHS: &[&str] = &[
+    "client.serverHost",
+    "client.serverPort",
+    "client.password",
+    "client.legacyAuth",
+    "client.maxFrameSize",
+    "client.udpIdleTimeout",
+    "client.connectTimeout",
+    "client.serverConnectTimeout",
+    "client.reloadInterval",
+    "client.drainTimeout",
+    "client.flowWindow",
+    "client.reconnect.initialDelay",
+    "client.reconnect.maxDelay",
+    "client.reconnect.multiplier",
+    "client.reconnect.jitter",
+    "client.reconnect.maxAttempts",
+    "client.heartbeat.interval",
+    "client.heartbeat.idleTimeout",
+    "client.write.batchSize",
+    "client.write.flushDelay",
+    "client.compression.level",
+    "client.compression.minSize",
+    "client.tls.enabled",
+    "client.tls.caPath",
+    "client.tls.serverName",
+    "client.tls.insecureSkipVerify",
+    "client.tls.certPath",
+    "client.tls.keyPath",
+    "log.path",
+    "log.errorPath",
+    "log.level",
+    "log.errorLevel",
+    "log.consoleLevel",
+    "log.format",
+    "log.rotation.maxSize",
+    "log.rotation.interval",
+    "log.rotation.maxFiles",
+    "log.rotation.compress",
+    "log.message.detail",
+    "log.message.payloadPreview",
+];
+
+/// 代理列表的字段, 以 `LDD_CLIENT_PROXIES_{序号}_{字段}` 配置
+const PROXY_FIELDS: &[&str] = &["host", "port", "protocol", "openPort", "compress", "e2eKey"];
+
+/// 字符串类型的配置项, 取值原样保留, 不按 YAML 标量解析
+const STRING_PATHS: &[&str] = &[
+    "client.serverHost",
+    "client.password",
+    "client.tls.caPath",
+    "client.tls.serverName",
+    "client.tls.certPath",
+    "client.tls.keyPath",
+    "log.path",
+    "log.errorPath",
+    "log.level",
+    "log.errorLevel",
+    "log.consoleLevel",
+    "log.format",
+    "log.rotation.interval",
+    "log.message.detail",
+];
+
+/// 字符串类型的代理字段
+const PROXY_STRING_FIELDS: &[&str] = &["host", "protocol", "e2eKey"];
+
+/// 按模块覆盖的日志级别, 形如 `a::b=debug,c=warn`
+const ENV_LOG_MODULES: &str = "LOG_MODULES";
+
+/// 配置路径对应的环境变量名(不含前缀)
+fn env_name(path: &str) -> String {
+    let mut name = String::new();
+    for (i, segment) in path.split('.').enumerate() {
+        if i > 0 {
+            name.push('_');
+        }
+        for c in segment.chars() {
+            if c.is_ascii_uppercase() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+    }
+    name
+}
+
+/// 以 `LDD_` 开头的环境变量覆盖配置; `LDD_CLIENT_*`/`LDD_LOG_*` 中无法识别的变量视为配置错误
+pub fn apply_env(root: &mut Value, vars: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+    let mut vars: Vec<(String, String)> = vars
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix(ENV_PREFIX)?.to_string(), value)))
+        .collect();
+    // 按变量名排序, 保证代理列表按序号展开
+    vars.sort();
+
+    for (key, value) in vars {
+        if let Some(path) = ENV_PATHS.iter().find(|path| env_name(path) == key) {
+            set_path(root, path, parse_value(path, &value))?;
+        } else if let Some(rest) = key.strip_prefix("CLIENT_PROXIES_") {
+            let path = proxy_path(rest).ok_or_else(|| {
+                ClientError::Config(format!("无法识别的环境变量 {}{}", ENV_PREFIX, key))
+            })?;
+            set_path(root, &path, parse_value(&path, &value))?;
+        } else if key == ENV_LOG_MODULES {
+            for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+                let (module, level) = entry.split_once('=').ok_or_else(|| {
+                    ClientError::Config(format!(
+                        "{}{} 的格式应为 module=level: {}",
+                        ENV_PREFIX, key, entry
+                    ))
+                })?;
+                set_segments(
+                    root,
+                    &["log", "modules", module.trim()],
+                    Value::String(level.trim().to_string()),
+                )?;
+            }
+        } else if key.starts_with("CLIENT_") || key.starts_with("LOG_") {
+            return Err(ClientError::Config(format!(
+                "无法识别的环境变量 {}{}",
+                ENV_PREFIX, key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `0_OPEN_PORT` 转换为 `client.proxies.0.openPort`
+fn proxy_path(rest: &str) -> Option<String> {
+    let (index, field) = rest.split_once('_')?;
+    let index: usize = index.parse().ok()?;
+    let field = PROXY_FIELDS
+        .iter()
+        .find(|candidate| env_name(candidate) == field)?;
+    Some(format!("client.proxies.{}.{}", index, field))
+}
+
+/// 解析命令行或环境变量中配置项的值
+///
+/// 字符串类型的配置项原样保留, 避免 `0x1F`、`1e3` 等取值被当作数字改写;
+/// 其余配置项以 YAML 标量解析, 非标量按字符串处理
+pub fn parse_value(path: &str, raw: &str) -> Value {
+    if is_string_path(path) {
+        return Value::String(raw.to_string());
+    }
+    match serde_yaml::from_str::<Value>(raw) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_) | Value::String(_))) => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// 配置项是否为字符串类型
+fn is_string_path(path: &str) -> bool {
+    if STRING_PATHS.contains(&path) || path.starts_with("log.modules.") {
+        return true;
+    }
+    let mut segments = path.split('.');
+    match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some("client"), Some("proxies"), Some(index), Some(field), None) => {
+            index.parse::<usize>().is_ok() && PROXY_STRING_FIELDS.contains(&field)
+        }
+        _ => false,
+    }
+}
+
+/// 按以 `.` 分隔的路径写入配置, 数字段表示列表下标
+pub fn set_path(root: &mut Value, path: &str, value: Value) -> Result<()> {
+    let segments: Vec<&str> = path.split('.').collect();
+    set_segments(root, &segments, value)
+}
+
+fn set_segments(root: &mut Value, segments: &[&str], value: Value) -> Result<()> {
+    let mut node = root;
+    for (i, segment) in segments.iter().enumerate() {
+        let next = segments.get(i + 1);
+        let placeholder = match next {
+            Some(next) if next.parse::<usize>().is_ok() => Value::Sequence(Vec::new()),
+            Some(_) => Value::Mapping(Mapping::new()),
+            None => Value::Null,
+        };
+        node = match segment.parse::<usize>() {
+            Ok(index) => {
+                if node.is_null() {
+                    *node = Value::Sequence(Vec::new());
+                }
+                let sequence = node.as_sequence_mut().ok_or_else(|| {
+                    ClientError::Config(format!("配置项 {} 不是列表", segments[..i].join(".")))
+                })?;
+                if sequence.len() <= index {
+                    sequence.resize(index + 1, Value::Null);
+                }
+                let item = &mut sequence[index];
+                if item.is_null() {
+                    *item = placeholder;
+                }
+                item
+            }
+            Err(_) => {
+                if node.is_null() {
+                    *node = Value::Mapping(Mapping::new());
+                }
+                let mapping = node.as_mapping_mut().ok_or_else(|| {
+                    ClientError::Config(format!("配置项 {} 不是对象", segments[..i].join(".")))
+                })?;
+                let entry = mapping
+                    .entry(Value::String(segment.to_string()))
+                    .or_insert(Value::Null);
+                if entry.is_null() {
+                    *entry = placeholder;
+                }
+                entry
+            }
+        };
+    }
+    *node = value;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(vars: &[(&str, &str)]) -> Value {
+        let mut root = Value::Null;
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        apply_env(&mut root, vars).unwrap();
+        // 与加载配置时一样经文本中转
+        serde_yaml::from_str(&serde_yaml::to_string(&root).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn string_values_are_kept_verbatim() {
+        for password in ["0x1F", "007", "1e3", "+1", "true", "~"] {
+            let root = load(&[("LDD_CLIENT_PASSWORD", password)]);
+            assert_eq!(root["client"]["password"].as_str(), Some(password));
+        }
+        let key = "1".repeat(64);
+        let root = load(&[
+            ("LDD_CLIENT_SERVER_HOST", "1e3"),
+            ("LDD_CLIENT_PROXIES_0_HOST", "007"),
+            ("LDD_CLIENT_PROXIES_0_E2E_KEY", &key),
+        ]);
+        assert_eq!(root["client"]["serverHost"].as_str(), Some("1e3"));
+        assert_eq!(root["client"]["proxies"][0]["host"].as_str(), Some("007"));
+        assert_eq!(
+            root["client"]["proxies"][0]["e2eKey"].as_str(),
+            Some(key.as_str())
+        );
+    }
+
+    #[test]
+    fn typed_values_are_parsed() {
+        let root = load(&[
+            ("LDD_CLIENT_SERVER_PORT", "7000"),
+            ("LDD_CLIENT_TLS_ENABLED", "true"),
+            ("LDD_CLIENT_RECONNECT_MULTIPLIER", "1.5"),
+            ("LDD_CLIENT_PROXIES_0_OPEN_PORT", "8080"),
+        ]);
+        assert_eq!(root["client"]["serverPort"].as_u64(), Some(7000));
+        assert_eq!(root["client"]["tls"]["enabled"].as_bool(), Some(true));
+        assert_eq!(
+            root["client"]["reconnect"]["multiplier"].as_f64(),
+            Some(1.5)
+        );
+        assert_eq!(
+            root["client"]["proxies"][0]["openPort"].as_u64(),
+            Some(8080)
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// 心跳配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct HeartbeatConfig {
+    /// 心跳发送间隔(毫秒), 0 表示不主动发送心跳
+    #[serde(default = "default_interval")]
+    interval: u64,
+    /// 读空闲超时(毫秒), 超过该时间未收到服务端任何消息即认为连接已断开, 0 表示不检测
+    #[serde(rename = "idleTimeout", default = "default_idle_timeout")]
+    idle_timeout: u64,
+}
+
+fn default_interval() -> u64 {
+    30_000
+}
+
+fn default_idle_timeout() -> u64 {
+    90_000
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_interval(),
+            idle_timeout: default_idle_timeout(),
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn get_interval(&self) -> Option<Duration> {
+        match self.interval {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    pub fn get_idle_timeout(&self) -> Option<Duration> {
+        match self.idle_timeout {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+}
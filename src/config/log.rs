@@ -1,15 +1,156 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, fmt, io::Write};
 
 use chrono::Utc;
-use fern::Dispatch;
+use fern::{Dispatch, FormatCallback};
+use log::{LevelFilter, Record};
 use serde::Deserialize;
 
+use crate::helper::rolling::RollingFile;
+
 #[derive(Debug, Deserialize)]
 pub struct LogConfig {
     #[serde(rename = "errorPath", default = "default_error_path")]
     error_path: String,
     #[serde(default = "default_path")]
     path: String,
+    /// path 输出的日志级别
+    #[serde(default = "default_level")]
+    level: LevelFilter,
+    /// errorPath 输出的日志级别
+    #[serde(rename = "errorLevel", default = "default_error_level")]
+    error_level: LevelFilter,
+    /// 控制台输出的日志级别
+    #[serde(rename = "consoleLevel", default = "default_level")]
+    console_level: LevelFilter,
+    /// 按模块覆盖 path 与控制台输出的日志级别, 如 `ldd_nat_cross_rclient::net: debug`
+    #[serde(default)]
+    modules: BTreeMap<String, LevelFilter>,
+    #[serde(default)]
+    format: LogFormat,
+    /// 日志文件滚动策略, 同时作用于 path 与 errorPath
+    #[serde(default)]
+    rotation: RotationConfig,
+    /// 与服务端之间消息的日志详细程度
+    #[serde(default)]
+    message: MessageLogConfig,
+}
+
+/// 日志行格式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象
+    Json,
+}
+
+/// 按时间滚动的周期
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationInterval {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+/// 日志文件滚动配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct RotationConfig {
+    /// 单个日志文件的最大字节数, 0 表示不按大小滚动
+    #[serde(rename = "maxSize", default)]
+    max_size: u64,
+    #[serde(default)]
+    interval: RotationInterval,
+    /// 保留的历史文件数量
+    #[serde(rename = "maxFiles", default = "default_max_files")]
+    max_files: usize,
+    /// 是否以 gzip 压缩历史文件
+    #[serde(default)]
+    compress: bool,
+}
+
+fn default_max_files() -> usize {
+    7
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 0,
+            interval: RotationInterval::Never,
+            max_files: default_max_files(),
+            compress: false,
+        }
+    }
+}
+
+impl RotationConfig {
+    /// 单个日志文件的最大字节数, None 表示不按大小滚动
+    pub fn get_max_size(&self) -> Option<u64> {
+        match self.max_size {
+            0 => None,
+            n => Some(n),
+        }
+    }
+
+    pub fn get_interval(&self) -> RotationInterval {
+        self.interval
+    }
+
+    pub fn get_max_files(&self) -> usize {
+        self.max_files
+    }
+
+    pub fn is_compress(&self) -> bool {
+        self.compress
+    }
+
+    /// 是否需要滚动
+    pub fn is_enabled(&self) -> bool {
+        self.max_size > 0 || self.interval != RotationInterval::Never
+    }
+}
+
+/// 消息日志的详细程度
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageDetail {
+    /// 不记录消息
+    Off,
+    /// 仅记录指令类型、访问者与数据长度
+    #[default]
+    Summary,
+    /// 记录全部元数据, 敏感字段仍会脱敏
+    Full,
+}
+
+/// 消息日志配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MessageLogConfig {
+    #[serde(default)]
+    detail: MessageDetail,
+    /// Full 模式下以十六进制预览的数据字节数, 0 表示不预览
+    #[serde(rename = "payloadPreview", default)]
+    payload_preview: usize,
+}
+
+impl MessageLogConfig {
+    pub fn new(detail: MessageDetail, payload_preview: usize) -> Self {
+        Self {
+            detail,
+            payload_preview,
+        }
+    }
+
+    pub fn get_detail(&self) -> MessageDetail {
+        self.detail
+    }
+
+    pub fn get_payload_preview(&self) -> usize {
+        self.payload_preview
+    }
 }
 
 fn default_error_path() -> String {
@@ -20,9 +161,27 @@ fn default_path() -> String {
     "client.log".to_string()
 }
 
+fn default_level() -> LevelFilter {
+    LevelFilter::Info
+}
+
+fn default_error_level() -> LevelFilter {
+    LevelFilter::Error
+}
+
 impl LogConfig {
     pub fn new(error_path: String, path: String) -> Self {
-        Self { error_path, path }
+        Self {
+            error_path,
+            path,
+            level: default_level(),
+            error_level: default_error_level(),
+            console_level: default_level(),
+            modules: BTreeMap::new(),
+            format: LogFormat::default(),
+            rotation: RotationConfig::default(),
+            message: MessageLogConfig::default(),
+        }
     }
     pub fn get_error_path(&self) -> &str {
         self.error_path.as_str()
@@ -30,63 +189,105 @@ impl LogConfig {
     pub fn get_path(&self) -> &str {
         self.path.as_str()
     }
+    pub fn get_level(&self) -> LevelFilter {
+        self.level
+    }
+    pub fn get_error_level(&self) -> LevelFilter {
+        self.error_level
+    }
+    pub fn get_console_level(&self) -> LevelFilter {
+        self.console_level
+    }
+    pub fn get_modules(&self) -> &BTreeMap<String, LevelFilter> {
+        &self.modules
+    }
+    pub fn get_format(&self) -> LogFormat {
+        self.format
+    }
+    pub fn get_rotation(&self) -> &RotationConfig {
+        &self.rotation
+    }
+    pub fn get_message_config(&self) -> &MessageLogConfig {
+        &self.message
+    }
+}
+
+/// 打开日志文件, 配置了滚动策略时按策略滚动
+fn open_log_file(path: &str, rotation: &RotationConfig) -> std::io::Result<Box<dyn Write + Send>> {
+    if rotation.is_enabled() {
+        return Ok(Box::new(RollingFile::new(path, rotation.clone())?));
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    Ok(Box::new(file))
+}
+
+/// 按配置的格式输出一行日志
+fn format_line(
+    format: LogFormat,
+    with_target: bool,
+    out: FormatCallback,
+    message: &fmt::Arguments,
+    record: &Record,
+) {
+    let time = Utc::now().format("%Y-%m-%d %H:%M:%S"); // 时间戳
+    match format {
+        LogFormat::Text if with_target => out.finish(format_args!(
+            "[{}][{}][{}] {}",
+            time,
+            record.level(),
+            record.target(),
+            message
+        )),
+        LogFormat::Text => out.finish(format_args!("[{}][{}] {}", time, record.level(), message)),
+        LogFormat::Json => {
+            let line = serde_json::json!({
+                "time": time.to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": message.to_string(),
+            });
+            out.finish(format_args!("{}", line))
+        }
+    }
+}
+
+/// 为输出追加按模块覆盖的日志级别
+fn with_modules(mut dispatch: Dispatch, modules: &BTreeMap<String, LevelFilter>) -> Dispatch {
+    for (module, level) in modules {
+        dispatch = dispatch.level_for(module.clone(), *level);
+    }
+    dispatch
 }
 
 pub fn init_log(log_config: &LogConfig) -> Result<(), Box<dyn Error>> {
-    let error_path = log_config.get_error_path().to_string();
-    let path = log_config.get_path().to_string();
+    let format = log_config.get_format();
+    let rotation = log_config.get_rotation();
+    let modules = log_config.get_modules();
 
     // 配置App日志输出到文件
     let file_dispatch = Dispatch::new()
-        .chain(
-            std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)?,
-        )
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "[{}][{}][{}] {}",
-                Utc::now().format("%Y-%m-%d %H:%M:%S"), // 时间戳
-                record.level(),
-                record.target(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Info);
+        .chain(open_log_file(log_config.get_path(), rotation)?)
+        .format(move |out, message, record| format_line(format, true, out, message, record))
+        .level(log_config.get_level());
+    let file_dispatch = with_modules(file_dispatch, modules);
 
     // 设置错误日志输出格式
     let error_log_dispatch = Dispatch::new()
-        .chain(
-            std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(error_path)?,
-        )
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "[{}][{}][{}] {}",
-                Utc::now().format("%Y-%m-%d %H:%M:%S"), // 时间戳
-                record.level(),
-                record.target(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Error);
+        .chain(open_log_file(log_config.get_error_path(), rotation)?)
+        .format(move |out, message, record| format_line(format, true, out, message, record))
+        .level(log_config.get_error_level());
 
     // 设置控制台输出
     let console_log_dispatch = Dispatch::new()
         .chain(std::io::stdout()) // 输出到控制台
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "[{}][{}] {}",
-                Utc::now().format("%Y-%m-%d %H:%M:%S"), // 时间戳
-                record.level(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Info); // 控制台只打印 info 级别及以上日志
-                                        // 配置日志输出到控制台和文件
+        .format(move |out, message, record| format_line(format, false, out, message, record))
+        .level(log_config.get_console_level());
+    let console_log_dispatch = with_modules(console_log_dispatch, modules);
+
+    // 配置日志输出到控制台和文件
     fern::Dispatch::new()
         .chain(console_log_dispatch) // 控制台输出
         .chain(file_dispatch) // 文件输出
@@ -1,3 +1,11 @@
 pub mod arg;
 pub mod client;
+pub mod compression;
+pub mod env;
+pub mod heartbeat;
 pub mod log;
+pub mod reconnect;
+pub mod tls;
+pub mod validate;
+pub mod watch;
+pub mod write;
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// 断线重连配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReconnectConfig {
+    /// 首次重连前的等待时间(毫秒)
+    #[serde(rename = "initialDelay", default = "default_initial_delay")]
+    initial_delay: u64,
+    /// 重连等待时间上限(毫秒)
+    #[serde(rename = "maxDelay", default = "default_max_delay")]
+    max_delay: u64,
+    /// 每次失败后等待时间的增长倍数
+    #[serde(default = "default_multiplier")]
+    multiplier: f64,
+    /// 随机抖动比例, 取值 [0, 1]
+    #[serde(default = "default_jitter")]
+    jitter: f64,
+    /// 最大连续重连次数, 0 表示无限重试
+    #[serde(rename = "maxAttempts", default)]
+    max_attempts: u32,
+}
+
+fn default_initial_delay() -> u64 {
+    1000
+}
+
+fn default_max_delay() -> u64 {
+    60_000
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: default_initial_delay(),
+            max_delay: default_max_delay(),
+            multiplier: default_multiplier(),
+            jitter: default_jitter(),
+            max_attempts: 0,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    pub fn get_initial_delay(&self) -> Duration {
+        Duration::from_millis(self.initial_delay)
+    }
+
+    pub fn get_max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay)
+    }
+
+    pub fn get_multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    pub fn get_jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// 最大重连次数, None 表示无限重试
+    pub fn get_max_attempts(&self) -> Option<u32> {
+        match self.max_attempts {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+/// 与服务端之间的 TLS 配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TlsConfig {
+    /// 是否启用 TLS
+    #[serde(default)]
+    enabled: bool,
+    /// PEM 格式的 CA 证书路径, 未配置时使用内置的公共根证书
+    #[serde(rename = "caPath", default)]
+    ca_path: Option<String>,
+    /// 校验证书时使用的服务端名称, 未配置时使用 serverHost
+    #[serde(rename = "serverName", default)]
+    server_name: Option<String>,
+    /// 跳过服务端证书校验, 仅用于测试
+    #[serde(rename = "insecureSkipVerify", default)]
+    insecure_skip_verify: bool,
+    /// PEM 格式的客户端证书链路径, 用于双向 TLS 认证
+    #[serde(rename = "certPath", default)]
+    cert_path: Option<String>,
+    /// PEM 格式的客户端私钥路径
+    #[serde(rename = "keyPath", default)]
+    key_path: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_ca_path(&self) -> Option<&str> {
+        self.ca_path.as_deref()
+    }
+
+    pub fn get_server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    pub fn is_insecure_skip_verify(&self) -> bool {
+        self.insecure_skip_verify
+    }
+
+    pub fn get_cert_path(&self) -> Option<&str> {
+        self.cert_path.as_deref()
+    }
+
+    pub fn get_key_path(&self) -> Option<&str> {
+        self.key_path.as_deref()
+    }
+
+    /// 是否配置了客户端证书
+    pub fn has_client_cert(&self) -> bool {
+        self.cert_path.is_some() || self.key_path.is_some()
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    net::ToSocketAddrs,
+    path::Path,
+};
+
+use crate::{
+    client::process::{MAX_DATAGRAM_SIZE, MAX_TRANSFER_FRAME_SIZE},
+    common::error::{ClientError, Result},
+    model::{protocol::ProtocolEnum, proxy::ProxyConfig},
+    net::tls::load_client_auth,
+};
+
+use super::{client::ClientConfig, client::ConfigWrapper, log::LogConfig};
+
+/// 单个配置问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// 出错配置项的 YAML 路径, 如 `client.proxies[1].openPort`
+    pub path: String,
+    pub message: String,
+}
+
+/// 配置校验报告, 汇总所有问题后一次性给出
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    issues: Vec<ConfigIssue>,
+}
+
+impl ValidationReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ConfigIssue {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn issues(&self) -> &[ConfigIssue] {
+        &self.issues
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 没有问题时返回 Ok
+    pub fn into_result(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(ClientError::Validation(self))
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "配置校验发现 {} 个问题:", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  - {}: {}", issue.path, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// 校验选项
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidateOptions {
+    /// 是否解析主机名; 启动时不解析, 由重连处理临时的 DNS 故障
+    ///
+    /// 解析会阻塞当前线程, 在异步运行时中需放入 `spawn_blocking` 执行
+    pub resolve_hosts: bool,
+}
+
+/// 校验全部配置
+pub fn validate_config(config: &ConfigWrapper, options: ValidateOptions) -> ValidationReport {
+    let mut report = ValidationReport::new();
+    validate_client(config.get_client_config(), options, &mut report);
+    validate_log(config.get_log_config(), &mut report);
+    report
+}
+
+/// 校验客户端配置
+pub fn validate_client(
+    config: &ClientConfig,
+    options: ValidateOptions,
+    report: &mut ValidationReport,
+) {
+    let server_host = config.get_server_host();
+    let server_port = config.get_server_port();
+    check_host(
+        "client.serverHost",
+        server_host,
+        server_port,
+        options,
+        report,
+    );
+    check_port("client.serverPort", server_port, report);
+
+    if config.get_password().is_some_and(str::is_empty) {
+        report.push("client.password", "不能为空, 仅以证书认证时请删除该项");
+    }
+    if config.get_max_frame_size() < MAX_TRANSFER_FRAME_SIZE {
+        report.push(
+            "client.maxFrameSize",
+            format!(
+                "不能小于 {}, 否则无法容纳单条转发数据",
+                MAX_TRANSFER_FRAME_SIZE
+            ),
+        );
+    }
+    if (config.get_flow_window() as usize) < MAX_DATAGRAM_SIZE {
+        report.push(
+            "client.flowWindow",
+            format!(
+                "不能小于 {}, 否则无法容纳单个 UDP 数据报",
+                MAX_DATAGRAM_SIZE
+            ),
+        );
+    }
+    if config.get_udp_idle_timeout().is_zero() {
+        report.push("client.udpIdleTimeout", "必须大于 0");
+    }
+    if config.get_connect_timeout().is_zero() {
+        report.push("client.connectTimeout", "必须大于 0");
+    }
+    if config.get_server_connect_timeout().is_zero() {
+        report.push("client.serverConnectTimeout", "必须大于 0");
+    }
+
+    let reconnect = config.get_reconnect_config();
+    if reconnect.get_initial_delay() > reconnect.get_max_delay() {
+        report.push("client.reconnect.initialDelay", "不能大于 maxDelay");
+    }
+    if reconnect.get_multiplier() < 1.0 {
+        report.push("client.reconnect.multiplier", "不能小于 1");
+    }
+    if !(0.0..=1.0).contains(&reconnect.get_jitter()) {
+        report.push("client.reconnect.jitter", "取值范围为 [0, 1]");
+    }
+
+    let heartbeat = config.get_heartbeat_config();
+    if let (Some(interval), Some(idle_timeout)) =
+        (heartbeat.get_interval(), heartbeat.get_idle_timeout())
+    {
+        if idle_timeout <= interval {
+            report.push(
+                "client.heartbeat.idleTimeout",
+                "必须大于心跳间隔 interval, 否则空闲连接会被误判为断开",
+            );
+        }
+    }
+
+    if config.get_compression_config().get_level() > 9 {
+        report.push("client.compression.level", "取值范围为 0-9");
+    }
+    if config.get_write_config().get_batch_size() == 0 {
+        report.push("client.write.batchSize", "必须大于 0");
+    }
+
+    let tls = config.get_tls_config();
+    if let Some(ca_path) = tls.get_ca_path() {
+        if !Path::new(ca_path).is_file() {
+            report.push("client.tls.caPath", format!("文件 {} 不存在", ca_path));
+        }
+    }
+    if tls.has_client_cert() && !tls.is_enabled() {
+        report.push("client.tls.enabled", "配置客户端证书时必须启用 tls");
+    }
+    if let Err(e) = load_client_auth(tls) {
+        report.push("client.tls", e.to_string());
+    }
+    if config.get_password().is_none() && !tls.has_client_cert() {
+        report.push(
+            "client.password",
+            "未配置 password 时必须配置客户端证书 tls.certPath/tls.keyPath",
+        );
+    }
+
+    validate_proxies("client.proxies", config.get_proxy(), options, report);
+}
+
+/// 校验代理列表, 开放端口不允许重复; `path` 为列表在配置中的位置
+pub fn validate_proxies(
+    path: &str,
+    proxies: &[ProxyConfig],
+    options: ValidateOptions,
+    report: &mut ValidationReport,
+) {
+    // 开放端口 -> 首次出现的下标
+    let mut open_ports: HashMap<i32, usize> = HashMap::new();
+    for (index, proxy) in proxies.iter().enumerate() {
+        let proxy_path = format!("{}[{}]", path, index);
+        validate_proxy(&proxy_path, proxy, options, report);
+        match open_ports.entry(proxy.open_port()) {
+            Entry::Occupied(first) => report.push(
+                format!("{}.openPort", proxy_path),
+                format!(
+                    "开放端口 {} 与 {}[{}] 重复",
+                    proxy.open_port(),
+                    path,
+                    first.get()
+                ),
+            ),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+    }
+}
+
+/// 校验单个代理配置, `path` 为其在配置中的位置
+pub fn validate_proxy(
+    path: &str,
+    proxy: &ProxyConfig,
+    options: ValidateOptions,
+    report: &mut ValidationReport,
+) {
+    check_host(
+        &format!("{}.host", path),
+        proxy.host(),
+        proxy.port(),
+        options,
+        report,
+    );
+    check_port(&format!("{}.port", path), proxy.port(), report);
+    check_port(&format!("{}.openPort", path), proxy.open_port(), report);
+    if let ProtocolEnum::Unknown(protocol) = proxy.protocol() {
+        report.push(
+            format!("{}.protocol", path),
+            format!("不支持的协议 {:?}, 可选 tcp/udp", protocol),
+        );
+    }
+    // 先压缩再加密会通过密文长度泄露明文内容, 密文也无法再压缩
+    if proxy.is_compress() && proxy.e2e_key().is_some() {
+        report.push(
+            format!("{}.compress", path),
+            "开启端到端加密(e2eKey)时不能同时开启压缩",
+        );
+    }
+}
+
+/// 校验日志配置
+pub fn validate_log(config: &LogConfig, report: &mut ValidationReport) {
+    if config.get_path().is_empty() {
+        report.push("log.path", "不能为空");
+    }
+    if config.get_error_path().is_empty() {
+        report.push("log.errorPath", "不能为空");
+    }
+    for module in config.get_modules().keys() {
+        if module.is_empty() {
+            report.push("log.modules", "模块名不能为空");
+        }
+    }
+}
+
+fn check_port(path: &str, port: i32, report: &mut ValidationReport) {
+    if !(1..=65535).contains(&port) {
+        report.push(path, format!("端口 {} 超出范围 1-65535", port));
+    }
+}
+
+fn check_host(
+    path: &str,
+    host: &str,
+    port: i32,
+    options: ValidateOptions,
+    report: &mut ValidationReport,
+) {
+    if host.trim().is_empty() {
+        report.push(path, "不能为空");
+        return;
+    }
+    if !options.resolve_hosts {
+        return;
+    }
+    let port = u16::try_from(port).unwrap_or(0);
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) if addrs.len() > 0 => {}
+        Ok(_) => report.push(path, format!("主机 {} 没有可用的地址", host)),
+        Err(e) => report.push(path, format!("无法解析主机 {}: {}", host, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(yaml: &str) -> Vec<ConfigIssue> {
+        let config: ConfigWrapper = serde_yaml::from_str(yaml).unwrap();
+        validate_config(&config, ValidateOptions::default())
+            .issues()
+            .to_vec()
+    }
+
+    fn issue<'a>(issues: &'a [ConfigIssue], path: &str) -> &'a ConfigIssue {
+        issues
+            .iter()
+            .find(|issue| issue.path == path)
+            .unwrap_or_else(|| panic!("缺少 {} 的问题: {:?}", path, issues))
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() {
+        let issues = validate(
+            r#"
+client:
+  serverHost: 127.0.0.1
+  serverPort: 8080
+  password: secret
+  proxies:
+    - { host: 127.0.0.1, port: 22, openPort: 9000, protocol: tcp }
+"#,
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_every_issue_at_once() {
+        let key = "1".repeat(64);
+        let issues = validate(&format!(
+            r#"
+client:
+  serverHost: " "
+  serverPort: 70000
+  password: secret
+  proxies:
+    - {{ host: 127.0.0.1, port: 22, openPort: 9000, protocol: tcp }}
+    - {{ host: "", port: 0, openPort: 9001, protocol: sctp }}
+    - {{ host: 127.0.0.1, port: 23, openPort: 9000, protocol: tcp, compress: true, e2eKey: "{}" }}
+"#,
+            key
+        ));
+        assert_eq!(issue(&issues, "client.serverHost").message, "不能为空");
+        assert!(issue(&issues, "client.serverPort")
+            .message
+            .contains("70000"));
+        assert_eq!(issue(&issues, "client.proxies[1].host").message, "不能为空");
+        assert!(issue(&issues, "client.proxies[1].port")
+            .message
+            .contains("超出范围"));
+        assert!(issue(&issues, "client.proxies[1].protocol")
+            .message
+            .contains("sctp"));
+        assert!(issue(&issues, "client.proxies[2].compress")
+            .message
+            .contains("e2eKey"));
+        // 重复的开放端口同时指出两处位置
+        assert_eq!(
+            issue(&issues, "client.proxies[2].openPort").message,
+            "开放端口 9000 与 client.proxies[0] 重复"
+        );
+        assert_eq!(issues.len(), 7, "{:?}", issues);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    sync::mpsc,
+    time::{self, Interval, MissedTickBehavior},
+};
+
+use crate::common::signal::Hangup;
+
+use super::{
+    arg::Args,
+    client::{load_config, ConfigWrapper},
+    validate::ValidateOptions,
+};
+
+/// 监听配置文件的修改与 SIGHUP, 每次变化后重新读取并校验配置, 通过返回的通道发出
+///
+/// 读取或校验失败时只记录日志, 继续使用当前配置
+pub fn watch_config(args: Args, interval: Option<Duration>) -> mpsc::Receiver<ConfigWrapper> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let path = args.get_config_path().map(PathBuf::from);
+        let mut last = path.as_deref().and_then(file_version);
+        let mut ticker = match (&path, interval) {
+            (Some(_), Some(period)) => {
+                let mut ticker = time::interval_at(time::Instant::now() + period, period);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Some(ticker)
+            }
+            _ => None,
+        };
+        let mut hangup = Hangup::new();
+
+        loop {
+            tokio::select! {
+                _ = next_tick(&mut ticker) => {
+                    let current = path.as_deref().and_then(file_version);
+                    if current == last {
+                        continue;
+                    }
+                    last = current;
+                    log::info!("检测到配置文件变化, 重新加载配置");
+                }
+                _ = hangup.recv() => log::info!("收到 SIGHUP, 重新加载配置"),
+            }
+            let result = load_config(&args).and_then(|config| {
+                config.validate(ValidateOptions::default())?;
+                Ok(config)
+            });
+            match result {
+                Ok(config) => {
+                    if tx.send(config).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::error!("重新加载配置失败, 继续使用当前配置: {}", e),
+            }
+        }
+    });
+    rx
+}
+
+/// 以修改时间与大小标识文件版本
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// 等待下一次检查; 未开启检查时永不返回
+async fn next_tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
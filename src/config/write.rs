@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// 向服务端写数据的合并配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct WriteConfig {
+    /// 单次写入合并的最大字节数, 达到后立即写出
+    #[serde(rename = "batchSize", default = "default_batch_size")]
+    batch_size: usize,
+    /// 队列为空时继续等待后续消息的时间(毫秒), 0 表示只合并已在排队的消息, 不增加延迟
+    #[serde(rename = "flushDelay", default)]
+    flush_delay: u64,
+}
+
+fn default_batch_size() -> usize {
+    64 * 1024
+}
+
+impl Default for WriteConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_batch_size(),
+            flush_delay: 0,
+        }
+    }
+}
+
+impl WriteConfig {
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    pub fn get_flush_delay(&self) -> Option<Duration> {
+        match self.flush_delay {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+}
@@ -23,6 +23,10 @@ pub enum CmdType {
     OpenServer = 7,
     /// 关闭代理端口
     CloseServer = 8,
+    /// 认证挑战
+    AuthChallenge = 9,
+    /// 归还访问者的流量控制额度
+    WindowUpdate = 10,
 }
 impl CmdType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -40,6 +44,8 @@ impl CmdType {
             Self::Transfer => "TRANSFER",
             Self::OpenServer => "OPEN_SERVER",
             Self::CloseServer => "CLOSE_SERVER",
+            Self::AuthChallenge => "AUTH_CHALLENGE",
+            Self::WindowUpdate => "WINDOW_UPDATE",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -54,6 +60,8 @@ impl CmdType {
             "TRANSFER" => Some(Self::Transfer),
             "OPEN_SERVER" => Some(Self::OpenServer),
             "CLOSE_SERVER" => Some(Self::CloseServer),
+            "AUTH_CHALLENGE" => Some(Self::AuthChallenge),
+            "WINDOW_UPDATE" => Some(Self::WindowUpdate),
             _ => None,
         }
     }
@@ -6,6 +6,6 @@ pub struct TransferDataMessage {
     pub cmd_type: i32,
     #[prost(message, optional, tag = "2")]
     pub meta_data: ::core::option::Option<super::meta_data::TransferMessageMetaData>,
-    #[prost(bytes = "vec", tag = "3")]
-    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "bytes", tag = "3")]
+    pub data: ::prost::bytes::Bytes,
 }
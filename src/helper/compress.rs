@@ -0,0 +1,96 @@
+use std::io::{self, Read, Write};
+
+use bytes::Bytes;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/// 协议中 `compression` 元数据的取值, 目前只支持 deflate
+pub const DEFLATE: &str = "deflate";
+
+/// TRANSFER 数据的压缩器
+#[derive(Debug, Clone, Copy)]
+pub struct Compressor {
+    level: u32,
+    min_size: usize,
+}
+
+impl Compressor {
+    /// `level` 为 0-9 的压缩级别, 短于 `min_size` 的数据不压缩
+    pub fn new(level: u32, min_size: usize) -> Self {
+        Self { level, min_size }
+    }
+
+    /// 压缩数据, 返回发送的数据以及是否经过压缩; 数据过短或压缩后没有变小时原样发送
+    pub fn compress(&self, data: Bytes) -> (Bytes, bool) {
+        if data.len() < self.min_size {
+            return (data, false);
+        }
+        let mut encoder =
+            DeflateEncoder::new(Vec::with_capacity(data.len()), Compression::new(self.level));
+        match encoder.write_all(&data).and_then(|_| encoder.finish()) {
+            Ok(compressed) if compressed.len() < data.len() => (Bytes::from(compressed), true),
+            _ => (data, false),
+        }
+    }
+}
+
+/// 解压数据, 解压后超过 `limit` 字节视为错误, 防止异常数据耗尽内存
+pub fn decompress(data: &[u8], limit: usize) -> io::Result<Bytes> {
+    let mut decompressed = Vec::with_capacity(data.len() * 4);
+    DeflateDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("解压后的数据超过 {} 字节", limit),
+        ));
+    }
+    Ok(Bytes::from(decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = Bytes::from("hello ".repeat(100));
+        let (compressed, flag) = Compressor::new(6, 16).compress(data.clone());
+        assert!(flag);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn short_data_is_sent_verbatim() {
+        let data = Bytes::from("a".repeat(15));
+        let (sent, flag) = Compressor::new(6, 16).compress(data.clone());
+        assert!(!flag);
+        assert_eq!(sent, data);
+    }
+
+    #[test]
+    fn incompressible_data_is_sent_verbatim() {
+        // 线性同余生成的伪随机字节, 压缩后不会变小
+        let mut state = 1u32;
+        let data: Bytes = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<_>>()
+            .into();
+        let (sent, flag) = Compressor::new(9, 16).compress(data.clone());
+        assert!(!flag);
+        assert_eq!(sent, data);
+    }
+
+    #[test]
+    fn decompress_rejects_output_above_limit() {
+        let data = Bytes::from(vec![0; 1024]);
+        let (compressed, _) = Compressor::new(6, 0).compress(data);
+        assert_eq!(decompress(&compressed, 1024).unwrap().len(), 1024);
+        let err = decompress(&compressed, 1023).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,330 @@
+use std::{fmt, io, str::FromStr};
+
+use bytes::Bytes;
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Deserializer};
+
+/// 预共享密钥长度(字节)
+const KEY_LEN: usize = 32;
+/// nonce 中随机前缀的长度, 其余 4 字节为计数器
+const NONCE_PREFIX_LEN: usize = 8;
+/// 认证标签长度
+const TAG_LEN: usize = 16;
+/// 本端为每个访问者选择的随机盐的长度, 参与派生访问者发往客户端方向的密钥
+pub const SALT_LEN: usize = 16;
+/// 每条加密数据比明文多出的长度: nonce + 认证标签
+pub const OVERHEAD: usize = aead::NONCE_LEN + TAG_LEN;
+/// 由预共享密钥派生数据密钥时使用的盐
+const HKDF_SALT: &[u8] = b"ldd-nat-cross e2e v1";
+/// 客户端发往访问者方向的密钥标签
+const CLIENT_TO_VISITOR: &[u8] = b"client->visitor";
+/// 访问者发往客户端方向的密钥标签
+const VISITOR_TO_CLIENT: &[u8] = b"visitor->client";
+
+/// 端到端加密的预共享密钥, 配置中以 64 位十六进制字符表示
+#[derive(Clone, PartialEq, Eq)]
+pub struct PresharedKey([u8; KEY_LEN]);
+
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 密钥不出现在日志中
+        f.write_str("PresharedKey(***)")
+    }
+}
+
+impl FromStr for PresharedKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut key = [0u8; KEY_LEN];
+        // 错误信息中不包含密钥内容
+        hex::decode_to_slice(s.trim(), &mut key)
+            .map_err(|_| format!("需要 {} 位十六进制字符({} 字节)", KEY_LEN * 2, KEY_LEN))?;
+        Ok(Self(key))
+    }
+}
+
+impl<'de> Deserialize<'de> for PresharedKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl PresharedKey {
+    /// 以 `info` 各段拼接的结果派生数据密钥
+    fn derive(&self, info: &[&[u8]]) -> LessSafeKey {
+        let prk = Salt::new(HKDF_SHA256, HKDF_SALT).extract(&self.0);
+        let okm = prk
+            .expand(info, &CHACHA20_POLY1305)
+            .expect("HKDF 输出长度与密钥长度一致");
+        LessSafeKey::new(UnboundKey::from(okm))
+    }
+}
+
+/// 为访问者创建双向的加解密状态
+///
+/// `strict` 为 true 时要求对端数据逐条连续(TCP), 否则只要求计数器递增, 允许丢失(UDP)。
+/// 访问者发往客户端方向的密钥混入本端随机选择的盐, 盐随 CONNECT 应答发给对端,
+/// 中转方录下的数据无法在之后的连接中重放
+pub fn stream(key: &PresharedKey, visitor_id: &str, strict: bool) -> io::Result<(Sealer, Opener)> {
+    let rng = SystemRandom::new();
+    let mut prefix = [0u8; NONCE_PREFIX_LEN];
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill(&mut prefix)
+        .and_then(|_| rng.fill(&mut salt))
+        .map_err(|_| io::Error::other("生成随机数失败"))?;
+    let sealer = Sealer {
+        key: key.derive(&[CLIENT_TO_VISITOR, b":", visitor_id.as_bytes()]),
+        prefix,
+        counter: 0,
+    };
+    let opener = Opener {
+        key: key.derive(&[VISITOR_TO_CLIENT, b":", visitor_id.as_bytes(), b":", &salt]),
+        salt,
+        peer_prefix: None,
+        next: 0,
+        strict,
+    };
+    Ok((sealer, opener))
+}
+
+/// 加密发往访问者的数据
+pub struct Sealer {
+    key: LessSafeKey,
+    /// 本端随机选择的 nonce 前缀, 避免 visitor_id 重复使用时 nonce 重复
+    prefix: [u8; NONCE_PREFIX_LEN],
+    counter: u32,
+}
+
+impl Sealer {
+    /// 加密一条数据, 输出 nonce(12) + 密文 + 认证标签(16)
+    pub fn seal(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let nonce = nonce(&self.prefix, self.counter);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "加密数据条数达到上限"))?;
+        let mut frame = Vec::with_capacity(data.len() + OVERHEAD);
+        frame.extend_from_slice(&nonce);
+        frame.extend_from_slice(data);
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut frame[aead::NONCE_LEN..],
+            )
+            .map_err(|_| io::Error::other("加密失败"))?;
+        frame.extend_from_slice(tag.as_ref());
+        Ok(Bytes::from(frame))
+    }
+}
+
+/// 解密访问者发来的数据, 拒绝伪造、重放与乱序的数据
+pub struct Opener {
+    key: LessSafeKey,
+    /// 参与派生密钥的随机盐, 需要通过 CONNECT 应答告知对端
+    salt: [u8; SALT_LEN],
+    /// 对端的 nonce 前缀, 由第一条通过认证的数据确定
+    peer_prefix: Option<[u8; NONCE_PREFIX_LEN]>,
+    /// 期望的下一个计数器
+    next: u32,
+    strict: bool,
+}
+
+impl Opener {
+    pub fn salt(&self) -> &[u8; SALT_LEN] {
+        &self.salt
+    }
+
+    pub fn open(&mut self, frame: &[u8]) -> io::Result<Bytes> {
+        if frame.len() < OVERHEAD {
+            return Err(invalid_data("加密数据长度不足"));
+        }
+        let (nonce, ciphertext) = frame.split_at(aead::NONCE_LEN);
+        let (prefix, counter) = nonce.split_at(NONCE_PREFIX_LEN);
+        if self
+            .peer_prefix
+            .is_some_and(|peer_prefix| peer_prefix != prefix)
+        {
+            return Err(invalid_data("nonce 前缀与之前的数据不一致"));
+        }
+        let mut buffer = ciphertext.to_vec();
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid_data("nonce 无效"))?;
+        let plaintext_len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut buffer)
+            .map_err(|_| invalid_data("认证失败, 密钥不一致或数据被篡改"))?
+            .len();
+        buffer.truncate(plaintext_len);
+
+        let counter = u32::from_be_bytes(counter.try_into().expect("计数器为 4 字节"));
+        let in_order = if self.strict {
+            counter == self.next
+        } else {
+            counter >= self.next
+        };
+        if !in_order {
+            return Err(invalid_data("数据被重放或乱序"));
+        }
+
+        // 通过认证后才更新状态, 伪造的数据不会影响后续数据
+        self.peer_prefix = Some(prefix.try_into().expect("前缀为 8 字节"));
+        self.next = counter
+            .checked_add(1)
+            .ok_or_else(|| invalid_data("解密数据条数达到上限"))?;
+        Ok(Bytes::from(buffer))
+    }
+}
+
+fn nonce(prefix: &[u8; NONCE_PREFIX_LEN], counter: u32) -> [u8; aead::NONCE_LEN] {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VISITOR_ID: &str = "visitor";
+
+    fn key() -> PresharedKey {
+        PresharedKey([7u8; KEY_LEN])
+    }
+
+    /// 访问者一侧的加密状态, 使用客户端 Opener 通告的盐
+    fn peer(key: &PresharedKey, opener: &Opener, prefix: [u8; NONCE_PREFIX_LEN]) -> Sealer {
+        Sealer {
+            key: key.derive(&[
+                VISITOR_TO_CLIENT,
+                b":",
+                VISITOR_ID.as_bytes(),
+                b":",
+                opener.salt(),
+            ]),
+            prefix,
+            counter: 0,
+        }
+    }
+
+    fn opener(strict: bool) -> Opener {
+        stream(&key(), VISITOR_ID, strict).unwrap().1
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut sealer, _) = stream(&key(), VISITOR_ID, true).unwrap();
+        // 访问者一侧解密客户端发出的数据
+        let mut peer_opener = Opener {
+            key: key().derive(&[CLIENT_TO_VISITOR, b":", VISITOR_ID.as_bytes()]),
+            salt: [0; SALT_LEN],
+            peer_prefix: None,
+            next: 0,
+            strict: true,
+        };
+        for data in [&b"hello"[..], b"", b"world"] {
+            let frame = sealer.seal(data).unwrap();
+            assert_eq!(frame.len(), data.len() + OVERHEAD);
+            assert_eq!(peer_opener.open(&frame).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_frame() {
+        let mut opener = opener(true);
+        let mut sealer = peer(&key(), &opener, [1; NONCE_PREFIX_LEN]);
+        let mut frame = sealer.seal(b"hello").unwrap().to_vec();
+        frame[aead::NONCE_LEN] ^= 1;
+        assert!(opener.open(&frame).is_err());
+        assert!(opener.open(&frame[..OVERHEAD - 1]).is_err());
+    }
+
+    #[test]
+    fn strict_rejects_replay_and_reorder() {
+        let mut opener = opener(true);
+        let mut sealer = peer(&key(), &opener, [1; NONCE_PREFIX_LEN]);
+        let first = sealer.seal(b"first").unwrap();
+        let second = sealer.seal(b"second").unwrap();
+        let third = sealer.seal(b"third").unwrap();
+
+        assert_eq!(opener.open(&first).unwrap(), &b"first"[..]);
+        assert!(opener.open(&first).is_err());
+        assert!(opener.open(&third).is_err());
+        assert_eq!(opener.open(&second).unwrap(), &b"second"[..]);
+        assert_eq!(opener.open(&third).unwrap(), &b"third"[..]);
+    }
+
+    #[test]
+    fn datagram_mode_accepts_gaps() {
+        let mut opener = opener(false);
+        let mut sealer = peer(&key(), &opener, [1; NONCE_PREFIX_LEN]);
+        let first = sealer.seal(b"first").unwrap();
+        let _lost = sealer.seal(b"lost").unwrap();
+        let third = sealer.seal(b"third").unwrap();
+
+        assert_eq!(opener.open(&first).unwrap(), &b"first"[..]);
+        assert_eq!(opener.open(&third).unwrap(), &b"third"[..]);
+        // 计数器仍然不能回退
+        assert!(opener.open(&first).is_err());
+    }
+
+    #[test]
+    fn forged_frame_does_not_change_state() {
+        let mut opener = opener(false);
+        let mut sealer = peer(&key(), &opener, [1; NONCE_PREFIX_LEN]);
+
+        // 用其他密钥伪造、前缀与计数器都不同的数据
+        let mut forger = peer(
+            &PresharedKey([8u8; KEY_LEN]),
+            &opener,
+            [2; NONCE_PREFIX_LEN],
+        );
+        forger.counter = 100;
+        let forged = forger.seal(b"forged").unwrap();
+        assert!(opener.open(&forged).is_err());
+        assert_eq!(opener.peer_prefix, None);
+        assert_eq!(opener.next, 0);
+
+        let frame = sealer.seal(b"hello").unwrap();
+        assert_eq!(opener.open(&frame).unwrap(), &b"hello"[..]);
+        assert_eq!(opener.peer_prefix, Some([1; NONCE_PREFIX_LEN]));
+        assert_eq!(opener.next, 1);
+
+        assert!(opener.open(&forged).is_err());
+        assert_eq!(opener.peer_prefix, Some([1; NONCE_PREFIX_LEN]));
+        assert_eq!(opener.next, 1);
+    }
+
+    #[test]
+    fn rejects_stream_recorded_for_another_connection() {
+        let mut recorded = opener(true);
+        let mut sealer = peer(&key(), &recorded, [1; NONCE_PREFIX_LEN]);
+        let frames: Vec<Bytes> = [&b"first"[..], b"second"]
+            .iter()
+            .map(|data| sealer.seal(data).unwrap())
+            .collect();
+        for frame in &frames {
+            assert!(recorded.open(frame).is_ok());
+        }
+
+        // 同一 visitor_id 的新连接使用新的盐, 录下的数据从第一条起就无法解密
+        let mut replayed = opener(true);
+        assert_ne!(replayed.salt(), recorded.salt());
+        for frame in &frames {
+            assert!(replayed.open(frame).is_err());
+        }
+    }
+}
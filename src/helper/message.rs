@@ -1,110 +1,50 @@
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
 use prost_types::Timestamp;
-use std::collections::HashMap;
+use sha2::Sha256;
 
 use crate::{
-    common::constants::{AUTH_PASSWORD, LICENSE_KEY, VISITOR_ID},
-    core::{
-        cmd_type::CmdType, meta_data::TransferMessageMetaData,
-        transfer_message::TransferDataMessage,
-    },
-    model::proxy::ProxyConfig,
+    common::error::{ClientError, Result},
+    core::{cmd_type::CmdType, transfer_message::TransferDataMessage},
 };
-/// 构建认证消息
-pub fn build_auth_message(password: &str) -> TransferDataMessage {
-    let mut meta_map: HashMap<String, String> = HashMap::new();
-    meta_map.insert(String::from(AUTH_PASSWORD), String::from(password));
-
-    let auth_meta = TransferMessageMetaData {
-        timestamp: Some(Timestamp::default()),
-        meta_data: meta_map,
-    };
-
-    let auth_message = TransferDataMessage {
-        cmd_type: CmdType::Auth as i32,
-        meta_data: Some(auth_meta),
-        data: [].to_vec(),
-    };
-    auth_message
-}
-
-/// 构建开放代理消息
-pub fn build_open_server_message(
-    proxy_config: &ProxyConfig,
-    license_key: String,
-) -> TransferDataMessage {
-    let mut meta_map = proxy_config.to_map();
-    meta_map.insert(String::from(LICENSE_KEY), String::from(license_key));
 
-    let open_server_meta = TransferMessageMetaData {
-        timestamp: Some(Timestamp::default()),
-        meta_data: meta_map,
-    };
-
-    let open_server_message = TransferDataMessage {
-        cmd_type: CmdType::OpenServer as i32,
-        meta_data: Some(open_server_meta),
-        data: [].to_vec(),
-    };
-    open_server_message
+/// 读取消息中必需的元数据
+pub fn get_meta_value<'a>(message: &'a TransferDataMessage, key: &'static str) -> Result<&'a str> {
+    message
+        .meta_data
+        .as_ref()
+        .and_then(|meta| meta.meta_data.get(key))
+        .map(String::as_str)
+        .ok_or_else(|| ClientError::MissingMetadata {
+            cmd_type: message.cmd_type(),
+            key,
+        })
 }
 
-/// 构建连接消息
-pub fn build_connect_message(
-    proxy_config: ProxyConfig,
-    license_key: String,
-    visitor_id: String,
-) -> TransferDataMessage {
-    let mut meta_map = proxy_config.to_map();
-    meta_map.insert(String::from(LICENSE_KEY), String::from(license_key));
-    meta_map.insert(VISITOR_ID.to_string(), visitor_id);
-
-    let meta_data = TransferMessageMetaData {
-        timestamp: Some(Timestamp::default()),
-        meta_data: meta_map,
-    };
-    let connect_message = TransferDataMessage {
-        cmd_type: CmdType::Connect as i32,
-        meta_data: Some(meta_data),
-        data: [].to_vec(),
-    };
-    connect_message
+/// 读取消息中的指令类型, 未知的指令视为协议错误
+pub fn get_cmd_type(message: &TransferDataMessage) -> Result<CmdType> {
+    CmdType::try_from(message.cmd_type)
+        .map_err(|_| ClientError::Protocol(format!("未知的指令类型 {}", message.cmd_type)))
 }
 
-/// 构建断开连接消息
-pub fn build_disconnect_message(license_key: String, visitor_id: String) -> TransferDataMessage {
-    let mut meta_map = HashMap::new();
-    meta_map.insert(LICENSE_KEY.to_string(), license_key);
-    meta_map.insert(VISITOR_ID.to_string(), visitor_id);
-
-    let meta_data = TransferMessageMetaData {
-        timestamp: Some(Timestamp::default()),
-        meta_data: meta_map,
-    };
-
-    TransferDataMessage {
-        cmd_type: CmdType::Disconnect as i32,
-        meta_data: Some(meta_data),
-        data: [].to_vec(),
-    }
+/// 计算认证挑战应答: HMAC-SHA256(secret, "{nonce}:{seconds}:{nanos}") 的十六进制小写形式
+///
+/// 时间戳参与计算, 服务端据此拒绝过期或重放的应答
+pub fn auth_proof(secret: &str, nonce: &str, timestamp: &Timestamp) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(format!("{}:{}:{}", nonce, timestamp.seconds, timestamp.nanos).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
 }
 
-/// 构建传输消息
-pub fn build_transfer_message(
-    data: Vec<u8>,
-    visitor_id: String,
-    license_key: String,
-) -> TransferDataMessage {
-    let mut meta_map = HashMap::new();
-    meta_map.insert(VISITOR_ID.to_string(), visitor_id);
-    meta_map.insert(LICENSE_KEY.to_string(), license_key);
-
-    let mt_data = TransferMessageMetaData {
-        timestamp: Some(Timestamp::default()),
-        meta_data: meta_map,
-    };
-    TransferDataMessage {
-        cmd_type: CmdType::Transfer as i32,
-        meta_data: Some(mt_data),
-        data,
-    }
+/// 时间戳与本机当前时间的偏差, 时间戳非法时返回 None
+pub fn timestamp_skew(timestamp: &Timestamp) -> Option<Duration> {
+    let time = SystemTime::try_from(*timestamp).ok()?;
+    let now = SystemTime::now();
+    Some(
+        now.duration_since(time)
+            .or_else(|_| time.duration_since(now))
+            .unwrap_or_default(),
+    )
 }
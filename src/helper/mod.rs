@@ -1 +1,5 @@
+pub mod compress;
+pub mod e2e;
 pub mod message;
+pub mod redact;
+pub mod rolling;
@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    common::constants::{AUTH_PASSWORD, AUTH_PROOF, LICENSE_KEY, VISITOR_ID},
+    config::log::{MessageDetail, MessageLogConfig},
+    core::transfer_message::TransferDataMessage,
+};
+
+/// 日志中需要脱敏的元数据
+const SENSITIVE_KEYS: [&str; 3] = [AUTH_PASSWORD, AUTH_PROOF, LICENSE_KEY];
+
+const MASK: &str = "***";
+
+/// 用于日志输出的消息视图: 敏感元数据脱敏, 数据仅输出长度与可选的十六进制预览
+pub struct Redacted<'a> {
+    message: &'a TransferDataMessage,
+    config: &'a MessageLogConfig,
+}
+
+impl<'a> Redacted<'a> {
+    pub fn new(message: &'a TransferDataMessage, config: &'a MessageLogConfig) -> Self {
+        Self { message, config }
+    }
+}
+
+/// 按配置记录一条与服务端之间的消息
+pub fn log_message(direction: &str, message: &TransferDataMessage, config: &MessageLogConfig) {
+    if config.get_detail() != MessageDetail::Off {
+        log::info!("{}: {}", direction, Redacted::new(message, config));
+    }
+}
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cmd_type = self.message.cmd_type().as_str_name();
+        let meta_data = self.message.meta_data.as_ref();
+        let data = &self.message.data;
+        match self.config.get_detail() {
+            MessageDetail::Off => Ok(()),
+            MessageDetail::Summary => {
+                write!(f, "{}", cmd_type)?;
+                if let Some(visitor_id) = meta_data.and_then(|meta| meta.meta_data.get(VISITOR_ID))
+                {
+                    write!(f, " visitor_id={}", visitor_id)?;
+                }
+                write!(f, " data={}B", data.len())
+            }
+            MessageDetail::Full => {
+                write!(f, "{}", cmd_type)?;
+                if let Some(meta) = meta_data {
+                    if let Some(timestamp) = &meta.timestamp {
+                        write!(f, " timestamp={}", timestamp)?;
+                    }
+                    // 按键排序, 保证输出稳定
+                    let entries: BTreeMap<_, _> = meta.meta_data.iter().collect();
+                    write!(f, " meta={{")?;
+                    for (i, (key, value)) in entries.into_iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        if SENSITIVE_KEYS.contains(&key.as_str()) {
+                            write!(f, "{}={}", key, MASK)?;
+                        } else {
+                            write!(f, "{}={}", key, value)?;
+                        }
+                    }
+                    write!(f, "}}")?;
+                }
+                write!(f, " data={}B", data.len())?;
+                let preview = self.config.get_payload_preview().min(data.len());
+                if preview > 0 {
+                    write!(f, " [{}", hex::encode(&data[..preview]))?;
+                    if preview < data.len() {
+                        write!(f, "..")?;
+                    }
+                    write!(f, "]")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::constants::AUTH_NONCE,
+        core::{cmd_type::CmdType, meta_data::TransferMessageMetaData},
+    };
+
+    fn message(data: &'static [u8]) -> TransferDataMessage {
+        let meta_data = [
+            (AUTH_PASSWORD, "password-value"),
+            (AUTH_PROOF, "proof-value"),
+            (LICENSE_KEY, "license-value"),
+            (AUTH_NONCE, "nonce-value"),
+            (VISITOR_ID, "visitor-value"),
+        ];
+        TransferDataMessage {
+            cmd_type: CmdType::Auth as i32,
+            meta_data: Some(TransferMessageMetaData {
+                timestamp: None,
+                meta_data: meta_data
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            }),
+            data: data.to_vec().into(),
+        }
+    }
+
+    fn render(message: &TransferDataMessage, detail: MessageDetail, preview: usize) -> String {
+        Redacted::new(message, &MessageLogConfig::new(detail, preview)).to_string()
+    }
+
+    #[test]
+    fn full_mode_masks_sensitive_metadata() {
+        let output = render(&message(b""), MessageDetail::Full, 0);
+        for key in SENSITIVE_KEYS {
+            assert!(output.contains(&format!("{}={}", key, MASK)), "{}", output);
+        }
+        for value in ["password-value", "proof-value", "license-value"] {
+            assert!(!output.contains(value), "{}", output);
+        }
+        assert!(output.contains("nonce-value"), "{}", output);
+    }
+
+    #[test]
+    fn full_mode_truncates_payload_preview() {
+        let data = b"\x01\x02\x03\x04";
+        assert!(render(&message(data), MessageDetail::Full, 2).ends_with("data=4B [0102..]"));
+        assert!(render(&message(data), MessageDetail::Full, 8).ends_with("data=4B [01020304]"));
+        assert!(render(&message(data), MessageDetail::Full, 0).ends_with("data=4B"));
+    }
+
+    #[test]
+    fn summary_mode_prints_only_visitor_id() {
+        let output = render(&message(b"abc"), MessageDetail::Summary, 16);
+        assert_eq!(output, "AUTH visitor_id=visitor-value data=3B");
+    }
+
+    #[test]
+    fn off_mode_prints_nothing() {
+        assert_eq!(render(&message(b"abc"), MessageDetail::Off, 16), "");
+    }
+}
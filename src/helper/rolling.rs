@@ -0,0 +1,198 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+
+use crate::config::log::{RotationConfig, RotationInterval};
+
+/// 按大小或时间滚动的日志文件
+///
+/// 滚动时当前文件依次改名为 `{path}.1`、`{path}.2` ...(开启压缩时追加 `.gz`),
+/// 超出保留数量的旧文件被删除
+pub struct RollingFile {
+    path: PathBuf,
+    rotation: RotationConfig,
+    file: File,
+    size: u64,
+    /// 当前文件所属的时间段, 时间段变化时滚动
+    period: Option<String>,
+    /// 上一次写入以换行结尾, 只在整行之间滚动
+    at_line_start: bool,
+}
+
+impl RollingFile {
+    pub fn new(path: impl AsRef<Path>, rotation: RotationConfig) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        let period = current_period(rotation.get_interval());
+        Ok(Self {
+            path,
+            rotation,
+            file,
+            size,
+            period,
+            at_line_start: true,
+        })
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        if let Some(max_size) = self.rotation.get_max_size() {
+            if self.size + incoming as u64 > max_size {
+                return true;
+            }
+        }
+        self.period.is_some() && self.period != current_period(self.rotation.get_interval())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let compress = self.rotation.is_compress();
+        let max_files = self.rotation.get_max_files();
+
+        // 删除超出保留数量的文件, 其余依次后移
+        let _ = fs::remove_file(self.rotated_path(max_files, compress));
+        for index in (1..max_files).rev() {
+            let from = self.rotated_path(index, compress);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1, compress))?;
+            }
+        }
+        if max_files > 0 {
+            if compress {
+                gzip(&self.path, &self.rotated_path(1, true))?;
+            } else {
+                fs::rename(&self.path, self.rotated_path(1, false))?;
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.period = current_period(self.rotation.get_interval());
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize, compress: bool) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        if compress {
+            name.push(".gz");
+        }
+        PathBuf::from(name)
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_line_start && self.should_rotate(buf.len()) {
+            // 滚动失败时继续写入当前文件, 避免丢失日志
+            if let Err(e) = self.rotate() {
+                eprintln!("日志文件 {} 滚动失败: {}", self.path.display(), e);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// 当前时间段的标识, 不按时间滚动时返回 None
+fn current_period(interval: RotationInterval) -> Option<String> {
+    let now = Utc::now();
+    match interval {
+        RotationInterval::Never => None,
+        RotationInterval::Hourly => Some(now.format("%Y-%m-%d %H").to_string()),
+        RotationInterval::Daily => Some(now.format("%Y-%m-%d").to_string()),
+    }
+}
+
+/// 将文件压缩到目标路径并删除原文件
+fn gzip(from: &Path, to: &Path) -> io::Result<()> {
+    let mut source = File::open(from)?;
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut source, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn rotation(yaml: &str) -> RotationConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    /// 写入 `lines` 行, 每行 10 字节, 行号作为内容便于区分各文件
+    fn write_lines(file: &mut RollingFile, lines: std::ops::Range<usize>) {
+        for line in lines {
+            file.write_all(format!("line {:04}\n", line).as_bytes())
+                .unwrap();
+        }
+        file.flush().unwrap();
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("client.log");
+        let mut file = RollingFile::new(&path, rotation("{maxSize: 30, maxFiles: 2}")).unwrap();
+        // 每个文件容纳 3 行, 共滚动 3 次
+        write_lines(&mut file, 0..12);
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "line 0009\nline 0010\nline 0011\n"
+        );
+        let rotated = |index| fs::read_to_string(path.with_extension(format!("log.{}", index)));
+        assert_eq!(rotated(1).unwrap(), "line 0006\nline 0007\nline 0008\n");
+        assert_eq!(rotated(2).unwrap(), "line 0003\nline 0004\nline 0005\n");
+        assert!(!path.with_extension("log.3").exists());
+    }
+
+    #[test]
+    fn compresses_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("client.log");
+        let mut file = RollingFile::new(
+            &path,
+            rotation("{maxSize: 30, maxFiles: 2, compress: true}"),
+        )
+        .unwrap();
+        write_lines(&mut file, 0..9);
+
+        let mut content = String::new();
+        GzDecoder::new(File::open(path.with_extension("log.1.gz")).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "line 0003\nline 0004\nline 0005\n");
+        assert!(path.with_extension("log.2.gz").exists());
+        assert!(!path.with_extension("log.1").exists());
+    }
+}
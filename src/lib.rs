@@ -1,5 +1,7 @@
+pub mod client;
 pub mod common;
 pub mod config;
 pub mod core;
 pub mod helper;
 pub mod model;
+pub mod net;
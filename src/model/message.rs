@@ -0,0 +1,683 @@
+use std::{collections::HashMap, str::FromStr, time::SystemTime};
+
+use bytes::Bytes;
+use prost_types::Timestamp;
+
+use crate::{
+    common::{
+        constants::{
+            AUTH_METHOD, AUTH_NONCE, AUTH_PASSWORD, AUTH_PROOF, COMPRESSED, COMPRESSION, E2E_SALT,
+            FLOW_WINDOW, HEARTBEAT_ACK, HEARTBEAT_ID, LICENSE_KEY, MESSAGE, OPEN_PORT, VISITOR_ID,
+            WINDOW_INCREMENT,
+        },
+        error::{ClientError, Result},
+    },
+    core::{
+        cmd_type::CmdType, meta_data::TransferMessageMetaData,
+        transfer_message::TransferDataMessage,
+    },
+    helper::message::get_cmd_type,
+    model::proxy::ProxyConfig,
+};
+
+/// 认证方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthMethod {
+    /// 明文发送共享密码, 仅用于兼容旧版服务端
+    Password(String),
+    /// 仅依赖双向 TLS 中的客户端证书
+    Certificate,
+    /// 请求服务端下发认证挑战
+    Challenge,
+    /// 对认证挑战的应答, `proof` 由 `timestamp` 参与计算, 发送时作为消息时间戳
+    Response {
+        nonce: String,
+        proof: String,
+        timestamp: Timestamp,
+    },
+}
+
+impl AuthMethod {
+    /// 协议中 `auth_method` 元数据的取值
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthMethod::Password(_) => "password",
+            AuthMethod::Certificate => "cert",
+            AuthMethod::Challenge | AuthMethod::Response { .. } => "hmac",
+        }
+    }
+}
+
+/// 强类型的协议消息
+///
+/// 与 `TransferDataMessage` 互相转换, 转换时校验各指令必需的元数据
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolMessage {
+    /// 心跳; `ack` 为 true 时表示对对端心跳的应答
+    Heartbeat {
+        heartbeat_id: Option<u64>,
+        license_key: Option<String>,
+        ack: bool,
+    },
+    /// 认证; `flow_window` 为本端单个访问者的接收窗口, 表示支持流量控制;
+    /// `compression` 为本端支持的压缩算法
+    Auth {
+        method: AuthMethod,
+        flow_window: Option<u32>,
+        compression: Option<String>,
+    },
+    /// 认证通过; 服务端支持流量控制时携带其单个访问者的接收窗口,
+    /// 支持压缩时携带选定的压缩算法
+    AuthOk {
+        license_key: String,
+        flow_window: Option<u32>,
+        compression: Option<String>,
+    },
+    /// 认证失败
+    AuthErr { reason: Option<String> },
+    /// 服务端下发的认证挑战
+    AuthChallenge {
+        nonce: String,
+        issued_at: Option<Timestamp>,
+    },
+    /// 访问者连接建立; 客户端的应答在开启端到端加密时携带十六进制的随机盐
+    Connect {
+        license_key: String,
+        visitor_id: String,
+        proxy: ProxyConfig,
+        e2e_salt: Option<String>,
+    },
+    /// 访问者连接断开
+    Disconnect {
+        license_key: String,
+        visitor_id: String,
+    },
+    /// 数据传输; 数据与接收缓冲区共享内存, 转发时不复制; `compressed` 表示数据经过压缩
+    Transfer {
+        license_key: String,
+        visitor_id: String,
+        data: Bytes,
+        compressed: bool,
+    },
+    /// 归还访问者的流量控制额度, 对端可以继续发送 `increment` 字节
+    WindowUpdate {
+        license_key: String,
+        visitor_id: String,
+        increment: u32,
+    },
+    /// 开放代理端口; 服务端应答时携带 `reason` 表示开放失败
+    OpenServer {
+        license_key: Option<String>,
+        open_port: i32,
+        proxy: Option<ProxyConfig>,
+        reason: Option<String>,
+    },
+    /// 关闭代理端口
+    CloseServer {
+        license_key: Option<String>,
+        open_port: i32,
+        proxy: Option<ProxyConfig>,
+        reason: Option<String>,
+    },
+}
+
+impl ProtocolMessage {
+    pub fn cmd_type(&self) -> CmdType {
+        match self {
+            ProtocolMessage::Heartbeat { .. } => CmdType::Heartbeat,
+            ProtocolMessage::Auth { .. } => CmdType::Auth,
+            ProtocolMessage::AuthOk { .. } => CmdType::AuthOk,
+            ProtocolMessage::AuthErr { .. } => CmdType::AuthErr,
+            ProtocolMessage::AuthChallenge { .. } => CmdType::AuthChallenge,
+            ProtocolMessage::Connect { .. } => CmdType::Connect,
+            ProtocolMessage::Disconnect { .. } => CmdType::Disconnect,
+            ProtocolMessage::Transfer { .. } => CmdType::Transfer,
+            ProtocolMessage::WindowUpdate { .. } => CmdType::WindowUpdate,
+            ProtocolMessage::OpenServer { .. } => CmdType::OpenServer,
+            ProtocolMessage::CloseServer { .. } => CmdType::CloseServer,
+        }
+    }
+
+    /// 构建开放代理端口请求
+    pub fn open_server(proxy_config: &ProxyConfig, license_key: String) -> Self {
+        ProtocolMessage::OpenServer {
+            license_key: Some(license_key),
+            open_port: proxy_config.open_port(),
+            proxy: Some(proxy_config.clone()),
+            reason: None,
+        }
+    }
+
+    /// 构建关闭代理端口请求
+    pub fn close_server(proxy_config: &ProxyConfig, license_key: String) -> Self {
+        ProtocolMessage::CloseServer {
+            license_key: Some(license_key),
+            open_port: proxy_config.open_port(),
+            proxy: Some(proxy_config.clone()),
+            reason: None,
+        }
+    }
+}
+
+/// 按指令类型读取元数据
+struct MetaReader {
+    cmd_type: CmdType,
+    meta_data: HashMap<String, String>,
+}
+
+impl MetaReader {
+    fn required(&mut self, key: &'static str) -> Result<String> {
+        self.meta_data
+            .remove(key)
+            .ok_or(ClientError::MissingMetadata {
+                cmd_type: self.cmd_type,
+                key,
+            })
+    }
+
+    fn optional(&mut self, key: &str) -> Option<String> {
+        self.meta_data.remove(key)
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &'static str) -> Result<T> {
+        let value = self.required(key)?;
+        value.parse().map_err(|_| {
+            ClientError::Protocol(format!(
+                "{} 消息中的 {} 非法: {}",
+                self.cmd_type.as_str_name(),
+                key,
+                value
+            ))
+        })
+    }
+
+    fn optional_parse<T: FromStr>(&mut self, key: &'static str) -> Result<Option<T>> {
+        if !self.meta_data.contains_key(key) {
+            return Ok(None);
+        }
+        self.parse(key).map(Some)
+    }
+
+    /// 元数据中的代理配置, 字段不完整时返回 None
+    fn proxy(&self) -> Option<ProxyConfig> {
+        ProxyConfig::from_map(self.meta_data.clone())
+    }
+}
+
+impl TryFrom<TransferDataMessage> for ProtocolMessage {
+    type Error = ClientError;
+
+    fn try_from(message: TransferDataMessage) -> Result<Self> {
+        let cmd_type = get_cmd_type(&message)?;
+        let (timestamp, meta_data) = message
+            .meta_data
+            .map(|meta| (meta.timestamp, meta.meta_data))
+            .unwrap_or_default();
+        let mut meta = MetaReader {
+            cmd_type,
+            meta_data,
+        };
+        let protocol_message = match cmd_type {
+            CmdType::Heartbeat => ProtocolMessage::Heartbeat {
+                heartbeat_id: meta.optional_parse(HEARTBEAT_ID)?,
+                license_key: meta.optional(LICENSE_KEY),
+                ack: meta.optional(HEARTBEAT_ACK).is_some(),
+            },
+            CmdType::Auth => {
+                let method = match (meta.optional(AUTH_PASSWORD), meta.optional(AUTH_METHOD)) {
+                    (Some(password), _) => AuthMethod::Password(password),
+                    (None, Some(method)) if method == AuthMethod::Certificate.as_str() => {
+                        AuthMethod::Certificate
+                    }
+                    (None, Some(method)) if method == AuthMethod::Challenge.as_str() => {
+                        match meta.optional(AUTH_PROOF) {
+                            Some(proof) => AuthMethod::Response {
+                                nonce: meta.required(AUTH_NONCE)?,
+                                proof,
+                                timestamp: timestamp.ok_or_else(|| {
+                                    ClientError::Protocol(String::from("AUTH 应答消息缺少时间戳"))
+                                })?,
+                            },
+                            None => AuthMethod::Challenge,
+                        }
+                    }
+                    _ => {
+                        return Err(ClientError::MissingMetadata {
+                            cmd_type,
+                            key: AUTH_PASSWORD,
+                        })
+                    }
+                };
+                ProtocolMessage::Auth {
+                    method,
+                    flow_window: meta.optional_parse(FLOW_WINDOW)?,
+                    compression: meta.optional(COMPRESSION),
+                }
+            }
+            CmdType::AuthOk => ProtocolMessage::AuthOk {
+                license_key: meta.required(LICENSE_KEY)?,
+                flow_window: meta.optional_parse(FLOW_WINDOW)?,
+                compression: meta.optional(COMPRESSION),
+            },
+            CmdType::AuthErr => ProtocolMessage::AuthErr {
+                reason: meta.optional(MESSAGE),
+            },
+            CmdType::AuthChallenge => ProtocolMessage::AuthChallenge {
+                nonce: meta.required(AUTH_NONCE)?,
+                issued_at: timestamp,
+            },
+            CmdType::Connect => {
+                let proxy = meta.proxy().ok_or_else(|| {
+                    ClientError::Protocol(String::from("CONNECT 消息中的代理配置不完整"))
+                })?;
+                ProtocolMessage::Connect {
+                    license_key: meta.required(LICENSE_KEY)?,
+                    visitor_id: meta.required(VISITOR_ID)?,
+                    proxy,
+                    e2e_salt: meta.optional(E2E_SALT),
+                }
+            }
+            CmdType::Disconnect => ProtocolMessage::Disconnect {
+                license_key: meta.required(LICENSE_KEY)?,
+                visitor_id: meta.required(VISITOR_ID)?,
+            },
+            CmdType::Transfer => ProtocolMessage::Transfer {
+                license_key: meta.required(LICENSE_KEY)?,
+                visitor_id: meta.required(VISITOR_ID)?,
+                data: message.data,
+                compressed: meta.optional(COMPRESSED).is_some_and(|flag| flag == "true"),
+            },
+            CmdType::WindowUpdate => ProtocolMessage::WindowUpdate {
+                license_key: meta.required(LICENSE_KEY)?,
+                visitor_id: meta.required(VISITOR_ID)?,
+                increment: meta.parse(WINDOW_INCREMENT)?,
+            },
+            CmdType::OpenServer => ProtocolMessage::OpenServer {
+                proxy: meta.proxy(),
+                open_port: meta.parse(OPEN_PORT)?,
+                license_key: meta.optional(LICENSE_KEY),
+                reason: meta.optional(MESSAGE),
+            },
+            CmdType::CloseServer => ProtocolMessage::CloseServer {
+                proxy: meta.proxy(),
+                open_port: meta.parse(OPEN_PORT)?,
+                license_key: meta.optional(LICENSE_KEY),
+                reason: meta.optional(MESSAGE),
+            },
+        };
+        Ok(protocol_message)
+    }
+}
+
+/// 代理端口相关消息的元数据
+fn server_meta(
+    license_key: Option<String>,
+    open_port: i32,
+    proxy: Option<ProxyConfig>,
+    reason: Option<String>,
+) -> HashMap<String, String> {
+    let mut meta_map = proxy.map(|proxy| proxy.to_map()).unwrap_or_default();
+    meta_map.insert(OPEN_PORT.to_string(), open_port.to_string());
+    if let Some(license_key) = license_key {
+        meta_map.insert(LICENSE_KEY.to_string(), license_key);
+    }
+    if let Some(reason) = reason {
+        meta_map.insert(MESSAGE.to_string(), reason);
+    }
+    meta_map
+}
+
+impl From<ProtocolMessage> for TransferDataMessage {
+    fn from(message: ProtocolMessage) -> Self {
+        let cmd_type = message.cmd_type();
+        // 挑战应答的时间戳参与了 proof 计算, 必须原样发送
+        let timestamp = match &message {
+            ProtocolMessage::Auth {
+                method: AuthMethod::Response { timestamp, .. },
+                ..
+            } => *timestamp,
+            ProtocolMessage::AuthChallenge {
+                issued_at: Some(issued_at),
+                ..
+            } => *issued_at,
+            _ => Timestamp::from(SystemTime::now()),
+        };
+        let mut meta_map = HashMap::new();
+        let mut data = Bytes::new();
+        match message {
+            ProtocolMessage::Heartbeat {
+                heartbeat_id,
+                license_key,
+                ack,
+            } => {
+                if let Some(heartbeat_id) = heartbeat_id {
+                    meta_map.insert(HEARTBEAT_ID.to_string(), heartbeat_id.to_string());
+                }
+                if let Some(license_key) = license_key {
+                    meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                }
+                if ack {
+                    meta_map.insert(HEARTBEAT_ACK.to_string(), true.to_string());
+                }
+            }
+            ProtocolMessage::Auth {
+                method,
+                flow_window,
+                compression,
+            } => {
+                if let Some(flow_window) = flow_window {
+                    meta_map.insert(FLOW_WINDOW.to_string(), flow_window.to_string());
+                }
+                if let Some(compression) = compression {
+                    meta_map.insert(COMPRESSION.to_string(), compression);
+                }
+                // 密码模式保持与旧版服务端兼容, 不携带 auth_method
+                if !matches!(method, AuthMethod::Password(_)) {
+                    meta_map.insert(AUTH_METHOD.to_string(), method.as_str().to_string());
+                }
+                match method {
+                    AuthMethod::Password(password) => {
+                        meta_map.insert(AUTH_PASSWORD.to_string(), password);
+                    }
+                    AuthMethod::Response { nonce, proof, .. } => {
+                        meta_map.insert(AUTH_NONCE.to_string(), nonce);
+                        meta_map.insert(AUTH_PROOF.to_string(), proof);
+                    }
+                    AuthMethod::Certificate | AuthMethod::Challenge => {}
+                }
+            }
+            ProtocolMessage::AuthOk {
+                license_key,
+                flow_window,
+                compression,
+            } => {
+                meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                if let Some(flow_window) = flow_window {
+                    meta_map.insert(FLOW_WINDOW.to_string(), flow_window.to_string());
+                }
+                if let Some(compression) = compression {
+                    meta_map.insert(COMPRESSION.to_string(), compression);
+                }
+            }
+            ProtocolMessage::AuthErr { reason } => {
+                if let Some(reason) = reason {
+                    meta_map.insert(MESSAGE.to_string(), reason);
+                }
+            }
+            ProtocolMessage::AuthChallenge { nonce, .. } => {
+                meta_map.insert(AUTH_NONCE.to_string(), nonce);
+            }
+            ProtocolMessage::Connect {
+                license_key,
+                visitor_id,
+                proxy,
+                e2e_salt,
+            } => {
+                meta_map = proxy.to_map();
+                meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                meta_map.insert(VISITOR_ID.to_string(), visitor_id);
+                if let Some(e2e_salt) = e2e_salt {
+                    meta_map.insert(E2E_SALT.to_string(), e2e_salt);
+                }
+            }
+            ProtocolMessage::Disconnect {
+                license_key,
+                visitor_id,
+            } => {
+                meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                meta_map.insert(VISITOR_ID.to_string(), visitor_id);
+            }
+            ProtocolMessage::Transfer {
+                license_key,
+                visitor_id,
+                data: payload,
+                compressed,
+            } => {
+                meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                meta_map.insert(VISITOR_ID.to_string(), visitor_id);
+                // 未压缩时不携带, 与旧版服务端保持一致
+                if compressed {
+                    meta_map.insert(COMPRESSED.to_string(), true.to_string());
+                }
+                data = payload;
+            }
+            ProtocolMessage::WindowUpdate {
+                license_key,
+                visitor_id,
+                increment,
+            } => {
+                meta_map.insert(LICENSE_KEY.to_string(), license_key);
+                meta_map.insert(VISITOR_ID.to_string(), visitor_id);
+                meta_map.insert(WINDOW_INCREMENT.to_string(), increment.to_string());
+            }
+            ProtocolMessage::OpenServer {
+                license_key,
+                open_port,
+                proxy,
+                reason,
+            }
+            | ProtocolMessage::CloseServer {
+                license_key,
+                open_port,
+                proxy,
+                reason,
+            } => {
+                meta_map = server_meta(license_key, open_port, proxy, reason);
+            }
+        }
+
+        TransferDataMessage {
+            cmd_type: cmd_type as i32,
+            meta_data: Some(TransferMessageMetaData {
+                timestamp: Some(timestamp),
+                meta_data: meta_map,
+            }),
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{helper::compress::DEFLATE, model::protocol::ProtocolEnum};
+
+    fn proxy() -> ProxyConfig {
+        ProxyConfig::new("127.0.0.1".to_string(), 8080, 9000, ProtocolEnum::TCP)
+    }
+
+    fn round_trip(message: ProtocolMessage) {
+        let decoded = ProtocolMessage::try_from(TransferDataMessage::from(message.clone()))
+            .expect("解析自身编码的消息");
+        assert_eq!(decoded, message);
+    }
+
+    /// 构造只有元数据的消息
+    fn raw(cmd_type: CmdType, meta: &[(&str, &str)]) -> TransferDataMessage {
+        TransferDataMessage {
+            cmd_type: cmd_type as i32,
+            meta_data: Some(TransferMessageMetaData {
+                timestamp: None,
+                meta_data: meta
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            }),
+            data: Bytes::new(),
+        }
+    }
+
+    fn missing_key(message: TransferDataMessage) -> &'static str {
+        match ProtocolMessage::try_from(message) {
+            Err(ClientError::MissingMetadata { key, .. }) => key,
+            other => panic!("期望缺少元数据, 实际为 {:?}", other),
+        }
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        let timestamp = Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 42,
+        };
+        let messages = vec![
+            ProtocolMessage::Heartbeat {
+                heartbeat_id: Some(7),
+                license_key: Some("key".to_string()),
+                ack: true,
+            },
+            ProtocolMessage::Heartbeat {
+                heartbeat_id: None,
+                license_key: None,
+                ack: false,
+            },
+            ProtocolMessage::Auth {
+                method: AuthMethod::Password("secret".to_string()),
+                flow_window: Some(65536),
+                compression: Some(DEFLATE.to_string()),
+            },
+            ProtocolMessage::Auth {
+                method: AuthMethod::Certificate,
+                flow_window: None,
+                compression: None,
+            },
+            ProtocolMessage::Auth {
+                method: AuthMethod::Challenge,
+                flow_window: None,
+                compression: None,
+            },
+            ProtocolMessage::Auth {
+                method: AuthMethod::Response {
+                    nonce: "nonce".to_string(),
+                    proof: "proof".to_string(),
+                    timestamp,
+                },
+                flow_window: Some(4096),
+                compression: None,
+            },
+            ProtocolMessage::AuthOk {
+                license_key: "key".to_string(),
+                flow_window: Some(65536),
+                compression: Some(DEFLATE.to_string()),
+            },
+            ProtocolMessage::AuthErr {
+                reason: Some("denied".to_string()),
+            },
+            ProtocolMessage::AuthChallenge {
+                nonce: "nonce".to_string(),
+                issued_at: Some(timestamp),
+            },
+            ProtocolMessage::Connect {
+                license_key: "key".to_string(),
+                visitor_id: "visitor".to_string(),
+                proxy: proxy().with_compress(true),
+                e2e_salt: Some("00ff".to_string()),
+            },
+            ProtocolMessage::Disconnect {
+                license_key: "key".to_string(),
+                visitor_id: "visitor".to_string(),
+            },
+            ProtocolMessage::Transfer {
+                license_key: "key".to_string(),
+                visitor_id: "visitor".to_string(),
+                data: Bytes::from_static(b"payload"),
+                compressed: true,
+            },
+            ProtocolMessage::WindowUpdate {
+                license_key: "key".to_string(),
+                visitor_id: "visitor".to_string(),
+                increment: 1024,
+            },
+            ProtocolMessage::open_server(&proxy(), "key".to_string()),
+            ProtocolMessage::OpenServer {
+                license_key: None,
+                open_port: 9000,
+                proxy: None,
+                reason: Some("in use".to_string()),
+            },
+            ProtocolMessage::close_server(&proxy(), "key".to_string()),
+        ];
+        for message in messages {
+            round_trip(message);
+        }
+    }
+
+    #[test]
+    fn reports_missing_required_metadata() {
+        assert_eq!(missing_key(raw(CmdType::AuthOk, &[])), LICENSE_KEY);
+        assert_eq!(
+            missing_key(raw(CmdType::Disconnect, &[(LICENSE_KEY, "key")])),
+            VISITOR_ID
+        );
+        assert_eq!(missing_key(raw(CmdType::OpenServer, &[])), OPEN_PORT);
+        assert_eq!(
+            missing_key(raw(
+                CmdType::WindowUpdate,
+                &[(LICENSE_KEY, "key"), (VISITOR_ID, "visitor")]
+            )),
+            WINDOW_INCREMENT
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        let auth_ok = raw(
+            CmdType::AuthOk,
+            &[(LICENSE_KEY, "key"), (FLOW_WINDOW, "large")],
+        );
+        assert!(matches!(
+            ProtocolMessage::try_from(auth_ok),
+            Err(ClientError::Protocol(_))
+        ));
+        let heartbeat = raw(CmdType::Heartbeat, &[(HEARTBEAT_ID, "-1")]);
+        assert!(matches!(
+            ProtocolMessage::try_from(heartbeat),
+            Err(ClientError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_cmd_type() {
+        let message = TransferDataMessage {
+            cmd_type: 9999,
+            meta_data: None,
+            data: Bytes::new(),
+        };
+        assert!(matches!(
+            ProtocolMessage::try_from(message),
+            Err(ClientError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn auth_response_keeps_proof_timestamp() {
+        let timestamp = Timestamp {
+            seconds: 1_600_000_000,
+            nanos: 123_456_789,
+        };
+        let message = TransferDataMessage::from(ProtocolMessage::Auth {
+            method: AuthMethod::Response {
+                nonce: "nonce".to_string(),
+                proof: "proof".to_string(),
+                timestamp,
+            },
+            flow_window: None,
+            compression: None,
+        });
+        assert_eq!(
+            message.meta_data.as_ref().and_then(|meta| meta.timestamp),
+            Some(timestamp)
+        );
+        let missing = TransferDataMessage {
+            meta_data: message.meta_data.map(|meta| TransferMessageMetaData {
+                timestamp: None,
+                ..meta
+            }),
+            ..message
+        };
+        assert!(matches!(
+            ProtocolMessage::try_from(missing),
+            Err(ClientError::Protocol(_))
+        ));
+    }
+}
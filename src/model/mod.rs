@@ -1,2 +1,4 @@
+pub mod message;
 pub mod protocol;
 pub mod proxy;
+pub mod tunnel;
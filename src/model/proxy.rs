@@ -1,16 +1,23 @@
 use serde::Deserialize;
 
 use crate::common::constants;
+use crate::helper::e2e::PresharedKey;
 use crate::model::protocol::ProtocolEnum;
 use std::collections::HashMap;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 pub struct ProxyConfig {
     host: String,
     port: i32,
     #[serde(rename = "openPort")]
     open_port: i32,
     protocol: ProtocolEnum,
+    /// 是否压缩该代理转发的数据, 服务端支持压缩时生效
+    #[serde(default)]
+    compress: bool,
+    /// 端到端加密的预共享密钥, 只在本地配置, 不会发送给服务端
+    #[serde(rename = "e2eKey", default)]
+    e2e_key: Option<PresharedKey>,
 }
 
 impl ProxyConfig {
@@ -21,9 +28,23 @@ impl ProxyConfig {
             port,
             open_port,
             protocol,
+            compress: false,
+            e2e_key: None,
         }
     }
 
+    /// 开启或关闭数据压缩
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// 设置端到端加密的预共享密钥
+    pub fn with_e2e_key(mut self, e2e_key: Option<PresharedKey>) -> Self {
+        self.e2e_key = e2e_key;
+        self
+    }
+
     pub fn host(&self) -> &str {
         &self.host
     }
@@ -40,6 +61,14 @@ impl ProxyConfig {
         self.protocol.clone()
     }
 
+    pub fn is_compress(&self) -> bool {
+        self.compress
+    }
+
+    pub fn e2e_key(&self) -> Option<&PresharedKey> {
+        self.e2e_key.as_ref()
+    }
+
     pub fn to_map(&self) -> HashMap<String, String> {
         let mut data = HashMap::new();
         data.insert(constants::PROXY_HOST.to_string(), self.host.clone());
@@ -49,6 +78,10 @@ impl ProxyConfig {
             self.protocol.as_str().to_string(),
         );
         data.insert(constants::OPEN_PORT.to_string(), self.open_port.to_string());
+        // 未开启压缩时不携带, 与旧版服务端保持一致
+        if self.compress {
+            data.insert(constants::PROXY_COMPRESS.to_string(), true.to_string());
+        }
         data
     }
 
@@ -57,12 +90,86 @@ impl ProxyConfig {
         let port = data.get(constants::PROXY_PORT)?.parse().ok()?;
         let protocol = ProtocolEnum::of(data.get(constants::PROXY_PROTOCOL)?.as_str())?;
         let open_port = data.get(constants::OPEN_PORT)?.parse().ok()?;
+        let compress = data
+            .get(constants::PROXY_COMPRESS)
+            .is_some_and(|compress| compress == "true");
 
         Some(Self {
             host,
             port,
             open_port,
             protocol,
+            compress,
+            e2e_key: None,
         })
     }
 }
+
+/// 新旧代理列表的差异, 以开放端口区分代理; 同一端口的配置变化视为先移除再新增
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyDiff {
+    pub added: Vec<ProxyConfig>,
+    pub removed: Vec<ProxyConfig>,
+    /// 未变化的代理数量
+    pub unchanged: usize,
+}
+
+impl ProxyDiff {
+    pub fn between(current: &[ProxyConfig], target: &[ProxyConfig]) -> Self {
+        let mut diff = ProxyDiff::default();
+        for proxy in current {
+            match target.iter().find(|new| new.open_port == proxy.open_port) {
+                Some(new) if new == proxy => diff.unchanged += 1,
+                _ => diff.removed.push(proxy.clone()),
+            }
+        }
+        for proxy in target {
+            if !current.contains(proxy) {
+                diff.added.push(proxy.clone());
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(port: i32, open_port: i32) -> ProxyConfig {
+        ProxyConfig::new("127.0.0.1".to_string(), port, open_port, ProtocolEnum::TCP)
+    }
+
+    #[test]
+    fn diff_reports_added_and_removed() {
+        let current = [proxy(8080, 9000), proxy(8081, 9001)];
+        let target = [proxy(8080, 9000), proxy(8082, 9002)];
+        let diff = ProxyDiff::between(&current, &target);
+        assert_eq!(diff.added, vec![proxy(8082, 9002)]);
+        assert_eq!(diff.removed, vec![proxy(8081, 9001)]);
+        assert_eq!(diff.unchanged, 1);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn diff_replaces_proxy_changed_on_same_port() {
+        let current = [proxy(8080, 9000)];
+        let target = [proxy(8080, 9000).with_compress(true)];
+        let diff = ProxyDiff::between(&current, &target);
+        assert_eq!(diff.removed, vec![proxy(8080, 9000)]);
+        assert_eq!(diff.added, vec![proxy(8080, 9000).with_compress(true)]);
+        assert_eq!(diff.unchanged, 0);
+    }
+
+    #[test]
+    fn diff_of_unchanged_proxies_is_empty() {
+        let current = [proxy(8080, 9000), proxy(8081, 9001)];
+        let diff = ProxyDiff::between(&current, &current);
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged, 2);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::model::proxy::ProxyConfig;
+
+/// 隧道状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TunnelState {
+    /// 已发送 OPEN_SERVER, 等待服务端应答
+    Pending,
+    /// 服务端已开放端口
+    Live,
+    /// 服务端拒绝或关闭了该端口
+    Failed(String),
+}
+
+/// 已请求开放的隧道, 以服务端开放端口为键
+#[derive(Debug, Default)]
+pub struct TunnelTable {
+    tunnels: BTreeMap<i32, (ProxyConfig, TunnelState)>,
+    /// 已发送 CLOSE_SERVER、等待服务端确认的端口及次数
+    closing: HashMap<i32, usize>,
+}
+
+impl TunnelTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一个等待服务端应答的隧道
+    pub fn request(&mut self, proxy_config: ProxyConfig) {
+        self.tunnels.insert(
+            proxy_config.open_port(),
+            (proxy_config, TunnelState::Pending),
+        );
+    }
+
+    /// 更新隧道状态, 返回对应的代理配置; 未登记的端口返回 None
+    pub fn update(&mut self, open_port: i32, state: TunnelState) -> Option<&ProxyConfig> {
+        let (proxy_config, current) = self.tunnels.get_mut(&open_port)?;
+        *current = state;
+        Some(proxy_config)
+    }
+
+    /// 移除隧道, 返回对应的代理配置
+    pub fn remove(&mut self, open_port: i32) -> Option<ProxyConfig> {
+        self.tunnels
+            .remove(&open_port)
+            .map(|(proxy_config, _)| proxy_config)
+    }
+
+    /// 移除隧道并记录等待服务端确认关闭
+    pub fn close(&mut self, open_port: i32) -> Option<ProxyConfig> {
+        let proxy_config = self.remove(open_port)?;
+        *self.closing.entry(open_port).or_default() += 1;
+        Some(proxy_config)
+    }
+
+    /// 服务端的 CLOSE_SERVER 是否为对本端关闭请求的确认
+    pub fn take_close_ack(&mut self, open_port: i32) -> bool {
+        match self.closing.get_mut(&open_port) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    self.closing.remove(&open_port);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 已登记隧道的代理配置, 已关闭或未请求的端口返回 None
+    pub fn get(&self, open_port: i32) -> Option<&ProxyConfig> {
+        self.tunnels
+            .get(&open_port)
+            .map(|(proxy_config, _)| proxy_config)
+    }
+
+    pub fn state(&self, open_port: i32) -> Option<&TunnelState> {
+        self.tunnels.get(&open_port).map(|(_, state)| state)
+    }
+
+    /// 所有隧道都已得到服务端应答
+    pub fn is_settled(&self) -> bool {
+        !self.tunnels.is_empty()
+            && self
+                .tunnels
+                .values()
+                .all(|(_, state)| *state != TunnelState::Pending)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ProxyConfig, &TunnelState)> {
+        self.tunnels.values().map(|(config, state)| (config, state))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tunnels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tunnels.is_empty()
+    }
+
+    /// 打印当前所有隧道的状态
+    pub fn report(&self) {
+        for (proxy_config, state) in self.iter() {
+            let target = format!(
+                "{}://{}:{} -> :{}",
+                proxy_config.protocol().as_str(),
+                proxy_config.host(),
+                proxy_config.port(),
+                proxy_config.open_port()
+            );
+            match state {
+                TunnelState::Live => log::info!("隧道已开启: {}", target),
+                TunnelState::Pending => log::warn!("隧道等待服务端应答: {}", target),
+                TunnelState::Failed(reason) => {
+                    log::error!("隧道开启失败: {}, 原因: {}", target, reason)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::protocol::ProtocolEnum;
+
+    fn proxy(open_port: i32) -> ProxyConfig {
+        ProxyConfig::new("127.0.0.1".to_string(), 8080, open_port, ProtocolEnum::TCP)
+    }
+
+    #[test]
+    fn close_ack_matches_each_close_request() {
+        let mut tunnels = TunnelTable::new();
+        tunnels.request(proxy(9000));
+        assert!(tunnels.close(9000).is_some());
+        // 重新开放后再次关闭, 需要两次确认
+        tunnels.request(proxy(9000));
+        assert!(tunnels.close(9000).is_some());
+        assert!(tunnels.take_close_ack(9000));
+        assert!(tunnels.take_close_ack(9000));
+        assert!(!tunnels.take_close_ack(9000));
+    }
+
+    #[test]
+    fn close_ack_ignores_ports_not_closed_by_client() {
+        let mut tunnels = TunnelTable::new();
+        tunnels.request(proxy(9000));
+        assert!(!tunnels.take_close_ack(9000));
+        // 未登记的端口不会记录关闭请求
+        assert!(tunnels.close(9001).is_none());
+        assert!(!tunnels.take_close_ack(9001));
+        assert_eq!(tunnels.state(9000), Some(&TunnelState::Pending));
+    }
+
+    #[test]
+    fn closed_tunnel_is_no_longer_registered() {
+        let mut tunnels = TunnelTable::new();
+        tunnels.request(proxy(9000));
+        assert_eq!(tunnels.get(9000), Some(&proxy(9000)));
+        tunnels.close(9000);
+        assert_eq!(tunnels.get(9000), None);
+        assert!(tunnels.is_empty());
+    }
+}
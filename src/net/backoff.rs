@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::config::reconnect::ReconnectConfig;
+
+/// 带随机抖动的指数退避
+#[derive(Debug)]
+pub struct Backoff {
+    config: ReconnectConfig,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(config: ReconnectConfig) -> Self {
+        Self {
+            config,
+            attempts: 0,
+        }
+    }
+
+    /// 已连续失败的次数
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// 连接成功后重置计数
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// 计算下一次重连前的等待时间, 超过最大重连次数时返回 None
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if let Some(max_attempts) = self.config.get_max_attempts() {
+            if self.attempts >= max_attempts {
+                return None;
+            }
+        }
+        let exponent = self.attempts.min(i32::MAX as u32) as i32;
+        self.attempts = self.attempts.saturating_add(1);
+
+        let initial = self.config.get_initial_delay().as_secs_f64();
+        let max = self.config.get_max_delay().as_secs_f64();
+        let base = (initial * self.config.get_multiplier().max(1.0).powi(exponent)).min(max);
+
+        let jitter = self.config.get_jitter().clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+        Some(Duration::from_secs_f64((base * factor).min(max)))
+    }
+}
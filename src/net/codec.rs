@@ -0,0 +1,193 @@
+use bytes::{Buf, BytesMut};
+use prost::Message;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    common::error::{ClientError, Result},
+    core::transfer_message::TransferDataMessage,
+};
+
+/// 默认最大帧长度 (8 MiB)
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+/// varint 长度前缀最多占用的字节数
+const MAX_VARINT_LEN: usize = 10;
+
+/// `TransferDataMessage` 编解码器
+///
+/// 帧格式与 protobuf 的 `writeDelimitedTo` 一致: varint 长度前缀 + 消息体。
+/// 解码时会缓存不完整的帧, 一次读取中包含的多个帧会被依次解出。
+#[derive(Debug, Clone)]
+pub struct TransferMessageCodec {
+    max_frame_size: usize,
+}
+
+impl TransferMessageCodec {
+    pub fn new() -> Self {
+        Self::with_max_frame_size(DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl Default for TransferMessageCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 尝试从缓冲区头部读取 varint 长度前缀
+///
+/// 返回 `Ok(None)` 表示前缀尚未接收完整, 否则返回 (前缀字节数, 帧长度)
+fn peek_length_delimiter(src: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut length: u64 = 0;
+    for (i, byte) in src.iter().take(MAX_VARINT_LEN).enumerate() {
+        length |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            let length = usize::try_from(length)
+                .map_err(|_| ClientError::Protocol(String::from("帧长度溢出")))?;
+            return Ok(Some((i + 1, length)));
+        }
+    }
+    if src.len() >= MAX_VARINT_LEN {
+        return Err(ClientError::Protocol(String::from(
+            "非法的 varint 长度前缀",
+        )));
+    }
+    Ok(None)
+}
+
+impl Decoder for TransferMessageCodec {
+    type Item = TransferDataMessage;
+    type Error = ClientError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let (prefix_len, frame_len) = match peek_length_delimiter(src)? {
+            Some(delimiter) => delimiter,
+            None => return Ok(None),
+        };
+        if frame_len > self.max_frame_size {
+            return Err(ClientError::Protocol(format!(
+                "帧长度 {} 超过上限 {}",
+                frame_len, self.max_frame_size
+            )));
+        }
+
+        // 帧尚未接收完整, 预留空间等待下一次读取
+        let total_len = prefix_len + frame_len;
+        if src.len() < total_len {
+            src.reserve(total_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(prefix_len);
+        let frame = src.split_to(frame_len).freeze();
+        let message = TransferDataMessage::decode(frame)?;
+        Ok(Some(message))
+    }
+}
+
+impl Encoder<TransferDataMessage> for TransferMessageCodec {
+    type Error = ClientError;
+
+    fn encode(&mut self, item: TransferDataMessage, dst: &mut BytesMut) -> Result<()> {
+        let frame_len = item.encoded_len();
+        if frame_len > self.max_frame_size {
+            return Err(ClientError::Protocol(format!(
+                "帧长度 {} 超过上限 {}",
+                frame_len, self.max_frame_size
+            )));
+        }
+        dst.reserve(prost::length_delimiter_len(frame_len) + frame_len);
+        item.encode_length_delimited(dst)
+            .map_err(|e| ClientError::Protocol(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::model::message::ProtocolMessage;
+
+    fn transfer(data: &'static [u8]) -> TransferDataMessage {
+        ProtocolMessage::Transfer {
+            license_key: String::from("license"),
+            visitor_id: String::from("visitor"),
+            data: Bytes::from_static(data),
+            compressed: false,
+        }
+        .into()
+    }
+
+    fn encode(codec: &mut TransferMessageCodec, message: TransferDataMessage) -> BytesMut {
+        let mut buffer = BytesMut::new();
+        codec.encode(message, &mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn decodes_frame_split_byte_by_byte() {
+        let mut codec = TransferMessageCodec::new();
+        let message = transfer(b"hello");
+        let encoded = encode(&mut codec, message.clone());
+
+        let mut src = BytesMut::new();
+        for (i, byte) in encoded.iter().enumerate() {
+            src.extend_from_slice(&[*byte]);
+            let decoded = codec.decode(&mut src).unwrap();
+            if i + 1 < encoded.len() {
+                assert!(decoded.is_none());
+            } else {
+                assert_eq!(decoded, Some(message.clone()));
+            }
+        }
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decodes_two_frames_in_one_buffer() {
+        let mut codec = TransferMessageCodec::new();
+        let first = transfer(b"first");
+        let second = transfer(b"second");
+        let mut src = encode(&mut codec, first.clone());
+        src.extend_from_slice(&encode(&mut codec, second.clone()));
+
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(first));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(second));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_oversize_length_before_buffering() {
+        let mut codec = TransferMessageCodec::with_max_frame_size(16);
+        // 只有长度前缀, 帧体尚未到达
+        let mut src = BytesMut::new();
+        prost::encode_length_delimiter(17, &mut src).unwrap();
+        let capacity = src.capacity();
+
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(ClientError::Protocol(_))
+        ));
+        assert_eq!(src.capacity(), capacity);
+    }
+
+    #[test]
+    fn rejects_overlong_varint() {
+        let mut codec = TransferMessageCodec::new();
+        let mut src = BytesMut::from(&[0x80u8; MAX_VARINT_LEN + 1][..]);
+
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(ClientError::Protocol(_))
+        ));
+    }
+}
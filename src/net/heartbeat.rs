@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+/// 平滑 RTT 的权重, 与 TCP 的 SRTT 计算保持一致
+const SMOOTHING_FACTOR: f64 = 0.125;
+
+/// 心跳往返时间统计
+#[derive(Debug)]
+pub struct RttTracker {
+    /// 从随机值开始编号, 避免与服务端主动发起的心跳序号相同而被误认为应答
+    next_id: u64,
+    /// 尚未收到应答的心跳 (序号, 发送时间)
+    pending: Option<(u64, Instant)>,
+    last: Option<Duration>,
+    smoothed: Option<Duration>,
+}
+
+impl RttTracker {
+    pub fn new() -> Self {
+        Self {
+            next_id: rand::random(),
+            pending: None,
+            last: None,
+            smoothed: None,
+        }
+    }
+
+    /// 登记一次心跳发送, 返回本次心跳序号
+    ///
+    /// 若上一次心跳尚未收到应答, 同时返回其序号
+    pub fn start(&mut self) -> (u64, Option<u64>) {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let missed = self.pending.replace((id, Instant::now())).map(|(id, _)| id);
+        (id, missed)
+    }
+
+    /// 是否为等待中的心跳序号
+    pub fn is_pending(&self, id: u64) -> bool {
+        matches!(self.pending, Some((pending_id, _)) if pending_id == id)
+    }
+
+    /// 收到心跳应答, 返回本次 RTT; 序号不匹配时返回 None
+    pub fn complete(&mut self, id: u64) -> Option<Duration> {
+        if !self.is_pending(id) {
+            return None;
+        }
+        let (_, sent_at) = self.pending.take()?;
+        let rtt = sent_at.elapsed();
+        self.last = Some(rtt);
+        self.smoothed = Some(match self.smoothed {
+            Some(srtt) => srtt.mul_f64(1.0 - SMOOTHING_FACTOR) + rtt.mul_f64(SMOOTHING_FACTOR),
+            None => rtt,
+        });
+        Some(rtt)
+    }
+
+    pub fn last(&self) -> Option<Duration> {
+        self.last
+    }
+
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.smoothed
+    }
+}
+
+impl Default for RttTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_only_the_pending_heartbeat() {
+        let mut rtt = RttTracker::new();
+        let (first, missed) = rtt.start();
+        assert_eq!(missed, None);
+        let (second, missed) = rtt.start();
+        assert_eq!(missed, Some(first));
+        assert_eq!(second, first.wrapping_add(1));
+        assert!(rtt.complete(first).is_none());
+        assert!(rtt.complete(second).is_some());
+        assert!(!rtt.is_pending(second));
+        assert_eq!(rtt.last(), rtt.smoothed());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod backoff;
+pub mod codec;
+pub mod heartbeat;
+pub mod tls;
+pub mod transport;
+pub mod writer;
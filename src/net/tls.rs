@@ -0,0 +1,170 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use tokio_rustls::{
+    rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+        sign::CertifiedKey,
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+
+use crate::{
+    common::error::{ClientError, Result},
+    config::tls::TlsConfig,
+};
+
+/// 根据配置构建 TLS 连接器, 未启用 TLS 时返回 None
+pub fn build_connector(tls_config: &TlsConfig) -> Result<Option<TlsConnector>> {
+    if !tls_config.is_enabled() {
+        return Ok(None);
+    }
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| ClientError::Tls(e.to_string()))?;
+
+    let builder = if tls_config.is_insecure_skip_verify() {
+        log::warn!("已关闭服务端证书校验, 请勿在生产环境中使用");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+    } else {
+        builder.with_root_certificates(load_root_store(tls_config.get_ca_path())?)
+    };
+    let config = match load_client_auth(tls_config)? {
+        Some((cert_chain, key)) => builder
+            .with_client_auth_cert(cert_chain, key)
+            .map_err(|e| ClientError::Tls(format!("客户端证书无效: {}", e)))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Some(TlsConnector::from(Arc::new(config))))
+}
+
+/// 读取客户端证书链与私钥, 并校验二者是否匹配; 未配置时返回 None
+pub fn load_client_auth(
+    tls_config: &TlsConfig,
+) -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>> {
+    let (cert_path, key_path) = match (tls_config.get_cert_path(), tls_config.get_key_path()) {
+        (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+        (None, None) => return Ok(None),
+        _ => {
+            return Err(ClientError::Config(String::from(
+                "certPath 与 keyPath 必须同时配置",
+            )))
+        }
+    };
+    let cert_chain = load_certs(cert_path)?;
+    let key = load_private_key(key_path)?;
+
+    let signing_key = ring::default_provider()
+        .key_provider
+        .load_private_key(key.clone_key())
+        .map_err(|e| ClientError::Config(format!("私钥 {} 无效: {}", key_path, e)))?;
+    CertifiedKey::new(cert_chain.clone(), signing_key)
+        .keys_match()
+        .map_err(|_| {
+            ClientError::Config(format!(
+                "客户端证书 {} 与私钥 {} 不匹配",
+                cert_path, key_path
+            ))
+        })?;
+    Ok(Some((cert_chain, key)))
+}
+
+/// 解析校验证书时使用的服务端名称
+pub fn server_name(tls_config: &TlsConfig, server_host: &str) -> Result<ServerName<'static>> {
+    let name = tls_config.get_server_name().unwrap_or(server_host);
+    ServerName::try_from(name.to_string())
+        .map_err(|_| ClientError::Tls(format!("非法的服务端名称: {}", name)))
+}
+
+fn load_root_store(ca_path: Option<&str>) -> Result<RootCertStore> {
+    let mut root_store = RootCertStore::empty();
+    let ca_path = match ca_path {
+        Some(ca_path) => ca_path,
+        None => {
+            root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            return Ok(root_store);
+        }
+    };
+    for cert in load_certs(ca_path)? {
+        root_store
+            .add(cert)
+            .map_err(|e| ClientError::Tls(format!("CA 证书 {} 无效: {}", ca_path, e)))?;
+    }
+    Ok(root_store)
+}
+
+/// 读取 PEM 文件中的全部证书
+pub(crate) fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path)
+        .map_err(|e| ClientError::Config(format!("读取证书 {} 失败: {}", path, e)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| ClientError::Config(format!("解析证书 {} 失败: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(ClientError::Config(format!("{} 中没有证书", path)));
+    }
+    Ok(certs)
+}
+
+/// 读取 PEM 文件中的第一个私钥
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path)
+        .map_err(|e| ClientError::Config(format!("读取私钥 {} 失败: {}", path, e)))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| ClientError::Config(format!("解析私钥 {} 失败: {}", path, e)))?
+        .ok_or_else(|| ClientError::Config(format!("{} 中没有私钥", path)))
+}
+
+/// 不校验服务端证书, 仅校验握手签名本身
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
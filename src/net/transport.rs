@@ -0,0 +1,55 @@
+use std::io;
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    time,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    common::error::{ClientError, Result},
+    config::client::ClientConfig,
+    net::tls::server_name,
+};
+
+/// 与服务端之间的双向字节流
+pub trait ServerStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> ServerStream for T {}
+
+/// 建立与服务端的连接, 配置了 TLS 时在 TCP 之上完成握手; 超过 `serverConnectTimeout` 视为失败
+pub async fn connect(
+    client_config: &ClientConfig,
+    tls_connector: Option<&TlsConnector>,
+) -> Result<Box<dyn ServerStream>> {
+    let timeout = client_config.get_server_connect_timeout();
+    time::timeout(timeout, connect_server(client_config, tls_connector))
+        .await
+        .unwrap_or_else(|_| {
+            Err(ClientError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{:?} 内未能与服务端建立连接", timeout),
+            )))
+        })
+}
+
+async fn connect_server(
+    client_config: &ClientConfig,
+    tls_connector: Option<&TlsConnector>,
+) -> Result<Box<dyn ServerStream>> {
+    let server_host = client_config.get_server_host();
+    let server_addr = format!("{}:{}", server_host, client_config.get_server_port());
+    let tcp_stream = TcpStream::connect(server_addr).await?;
+    let tls_connector = match tls_connector {
+        Some(tls_connector) => tls_connector,
+        None => return Ok(Box::new(tcp_stream)),
+    };
+
+    let server_name = server_name(client_config.get_tls_config(), server_host)?;
+    let tls_stream = tls_connector
+        .connect(server_name, tcp_stream)
+        .await
+        .map_err(|e| ClientError::Tls(format!("TLS 握手失败: {}", e)))?;
+    Ok(Box::new(tls_stream))
+}
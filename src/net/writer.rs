@@ -0,0 +1,73 @@
+use futures::SinkExt;
+use prost::Message;
+use tokio::{
+    io::AsyncWrite,
+    sync::mpsc::{self, error::TryRecvError},
+    time::{self, Instant},
+};
+use tokio_util::codec::FramedWrite;
+
+use crate::{
+    common::error::Result,
+    config::{log::MessageLogConfig, write::WriteConfig},
+    core::transfer_message::TransferDataMessage,
+    helper::redact::log_message,
+    net::codec::TransferMessageCodec,
+};
+
+/// 将排队的消息写给服务端, 直到所有发送方释放
+///
+/// 每次写出前把已在排队的消息编码进同一块复用的缓冲区, 达到 `batchSize` 或队列为空时才写出,
+/// 大量小帧合并为少量系统调用; 配置 `flushDelay` 时队列为空后再等待一段时间以合并更多消息
+pub async fn write_frames<W: AsyncWrite + Unpin>(
+    mut writer: FramedWrite<W, TransferMessageCodec>,
+    mut rx: mpsc::Receiver<TransferDataMessage>,
+    config: WriteConfig,
+    message_log: MessageLogConfig,
+) -> Result<()> {
+    let batch_size = config.get_batch_size();
+    // 缓冲超过该值时 feed 会先写出已有数据
+    writer.set_backpressure_boundary(batch_size);
+    while let Some(msg) = rx.recv().await {
+        feed(&mut writer, msg, &message_log).await?;
+        let deadline = config.get_flush_delay().map(|delay| Instant::now() + delay);
+        while writer.write_buffer().len() < batch_size {
+            let msg = match rx.try_recv() {
+                Ok(msg) => msg,
+                Err(TryRecvError::Empty) => match deadline {
+                    Some(deadline) => match time::timeout_at(deadline, rx.recv()).await {
+                        Ok(Some(msg)) => msg,
+                        Ok(None) | Err(_) => break,
+                    },
+                    None => break,
+                },
+                Err(TryRecvError::Disconnected) => break,
+            };
+            feed(&mut writer, msg, &message_log).await?;
+        }
+        writer.flush().await?;
+    }
+    // 所有发送方都已释放, 刷新缓冲并关闭写方向
+    writer.close().await
+}
+
+async fn feed<W: AsyncWrite + Unpin>(
+    writer: &mut FramedWrite<W, TransferMessageCodec>,
+    msg: TransferDataMessage,
+    message_log: &MessageLogConfig,
+) -> Result<()> {
+    log_message("send to server", &msg, message_log);
+    // 单条消息超过帧上限时只丢弃该消息, 不影响连接上的其他消息
+    let frame_len = msg.encoded_len();
+    let max_frame_size = writer.encoder().max_frame_size();
+    if frame_len > max_frame_size {
+        log::error!(
+            "{} 消息长度 {} 超过帧上限 {}, 已丢弃",
+            msg.cmd_type().as_str_name(),
+            frame_len,
+            max_frame_size
+        );
+        return Ok(());
+    }
+    writer.feed(msg).await
+}